@@ -0,0 +1,197 @@
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+
+const TAB_WIDTH: usize = 8;
+
+// Turns the output of programs that color their output, like delta or git diff --color, into
+// styled lines. SGR escape sequences set the style, other escape sequences are dropped and tabs are
+// expanded since the terminal is not the one interpreting them.
+pub fn parse(text: &str) -> Vec<Spans<'static>> {
+    let mut lines = Vec::new();
+    let mut style = Style::default();
+    for line in text.lines() {
+        let mut spans = Vec::new();
+        let mut content = String::new();
+        let mut column = 0;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\u{1b}' => match chars.next() {
+                    // Control Sequence Introducer, parameters and then a final byte from @ to ~
+                    Some('[') => {
+                        let mut parameters = String::new();
+                        let mut last = None;
+                        for c in chars.by_ref() {
+                            if ('@'..='~').contains(&c) {
+                                last = Some(c);
+                                break;
+                            }
+                            parameters.push(c);
+                        }
+                        if last == Some('m') {
+                            let next = apply_sgr(style, &parameters);
+                            if next != style && !content.is_empty() {
+                                spans.push(Span::styled(std::mem::take(&mut content), style));
+                            }
+                            style = next;
+                        }
+                    }
+                    // Operating System Commands like hyperlinks run up to BEL or ESC \
+                    Some(']') => {
+                        while let Some(c) = chars.next() {
+                            if c == '\u{7}' {
+                                break;
+                            }
+                            if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                                chars.next();
+                                break;
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                '\t' => {
+                    let width = TAB_WIDTH - column % TAB_WIDTH;
+                    content.push_str(&" ".repeat(width));
+                    column += width;
+                }
+                '\r' => {}
+                c if c.is_control() => {}
+                c => {
+                    content.push(c);
+                    column += 1;
+                }
+            }
+        }
+        if !content.is_empty() {
+            spans.push(Span::styled(content, style));
+        }
+        lines.push(Spans::from(spans));
+    }
+    lines
+}
+
+fn apply_sgr(mut style: Style, parameters: &str) -> Style {
+    // ESC[m is the same as ESC[0m
+    if parameters.is_empty() {
+        return Style::default();
+    }
+    let mut codes = parameters
+        .split([';', ':'])
+        .map(|code| code.parse::<u16>().unwrap_or(0));
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            25 => style.remove_modifier(Modifier::SLOW_BLINK),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            29 => style.remove_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(named_color(code - 30)),
+            38 => match extended_color(&mut codes) {
+                Some(color) => style.fg(color),
+                None => style,
+            },
+            39 => style.fg(Color::Reset),
+            40..=47 => style.bg(named_color(code - 40)),
+            48 => match extended_color(&mut codes) {
+                Some(color) => style.bg(color),
+                None => style,
+            },
+            49 => style.bg(Color::Reset),
+            90..=97 => style.fg(named_color(code - 90 + 8)),
+            100..=107 => style.bg(named_color(code - 100 + 8)),
+            _ => style,
+        };
+    }
+    style
+}
+
+// 38;5;N picks from the 256 color palette and 38;2;R;G;B is a true color
+fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match codes.next()? {
+        5 => Some(Color::Indexed(codes.next()? as u8)),
+        2 => {
+            let (r, g, b) = (codes.next()?, codes.next()?, codes.next()?);
+            Some(Color::Rgb(r as u8, g as u8, b as u8))
+        }
+        _ => None,
+    }
+}
+
+fn named_color(index: u16) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::Gray,
+        8 => Color::DarkGray,
+        9 => Color::LightRed,
+        10 => Color::LightGreen,
+        11 => Color::LightYellow,
+        12 => Color::LightBlue,
+        13 => Color::LightMagenta,
+        14 => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(spans: &Spans) -> String {
+        spans.0.iter().map(|span| span.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn splits_spans_where_the_style_changes() {
+        let lines = parse("\u{1b}[31m-old\u{1b}[m\n\u{1b}[1;32m+new\u{1b}[0m rest");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].0.len(), 1);
+        assert_eq!(lines[0].0[0].content, "-old");
+        assert_eq!(lines[0].0[0].style, Style::default().fg(Color::Red));
+        assert_eq!(lines[1].0[0].content, "+new");
+        assert_eq!(
+            lines[1].0[0].style,
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(lines[1].0[1].content, " rest");
+        assert_eq!(lines[1].0[1].style, Style::default());
+    }
+
+    #[test]
+    fn reads_extended_colors() {
+        let lines = parse("\u{1b}[38;5;208ma\u{1b}[48;2;1;2;3mb");
+        assert_eq!(
+            lines[0].0[0].style,
+            Style::default().fg(Color::Indexed(208))
+        );
+        assert_eq!(
+            lines[0].0[1].style,
+            Style::default()
+                .fg(Color::Indexed(208))
+                .bg(Color::Rgb(1, 2, 3))
+        );
+    }
+
+    #[test]
+    fn drops_other_escape_sequences_and_expands_tabs() {
+        let lines = parse("\u{1b}]8;;http://example.com\u{1b}\\link\u{1b}]8;;\u{7}\u{1b}[K\tx\r");
+        assert_eq!(text(&lines[0]), "link    x");
+    }
+}
@@ -0,0 +1,54 @@
+use std::io::Write;
+
+use crate::terminal;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Sets the system clipboard with the OSC 52 escape sequence. The terminal does the copying, so
+// this works over ssh without any clipboard tools, and tmux passes it on to the outer terminal
+// when its set-clipboard option is on.
+pub fn copy(text: &str) -> std::io::Result<()> {
+    let mut output = terminal::output();
+    write!(output, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    output.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (*byte as u32) << (16 - 8 * index)
+        });
+        // Three bytes become four characters, with padding for a short final chunk
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - 6 * index)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_with_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn encodes_bytes_outside_of_ascii() {
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd]), "//79");
+        assert_eq!(base64("é".as_bytes()), "w6k=");
+    }
+}
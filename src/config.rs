@@ -0,0 +1,416 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::de::{DeserializeOwned, Deserializer, Error};
+use serde::Deserialize;
+use toml::value::{Table, Value};
+
+use crate::diff_settings::{DiffAlgorithm, DiffSettings, Whitespace};
+use crate::keymap::Keymap;
+use crate::theme::Theme;
+use crate::widgets::{Column, ColumnLayout};
+use crate::word_diff::WordDiffMode;
+
+// Settings that add up instead of replacing each other when set in both places
+const LISTS: &[&str] = &["args", "collapse"];
+
+// Settings from the config file, with gitt.* git config taking precedence over it and command
+// line flags taking precedence over both. Anything not set keeps gitt's built in default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    // Flags used as if they were given before the ones on the command line
+    #[serde(deserialize_with = "list")]
+    pub args: Vec<String>,
+    // Milliseconds between redraws while no key is pressed
+    #[serde(deserialize_with = "tick_rate")]
+    pub tick_rate: Option<u64>,
+    // A strftime format for the dates in the commit list, RFC 3339 when not set
+    #[serde(deserialize_with = "date_format")]
+    pub date_format: Option<String>,
+    #[serde(deserialize_with = "number")]
+    pub context: Option<u32>,
+    #[serde(deserialize_with = "parsed")]
+    pub diff_algorithm: Option<DiffAlgorithm>,
+    #[serde(deserialize_with = "parsed")]
+    pub whitespace: Option<Whitespace>,
+    #[serde(deserialize_with = "boolean")]
+    pub indent_heuristic: Option<bool>,
+    #[serde(deserialize_with = "boolean")]
+    pub renames: Option<bool>,
+    #[serde(deserialize_with = "boolean")]
+    pub copies: Option<bool>,
+    #[serde(deserialize_with = "number")]
+    pub similarity: Option<u16>,
+    #[serde(deserialize_with = "parsed")]
+    pub word_diff: Option<WordDiffMode>,
+    #[serde(deserialize_with = "boolean")]
+    pub syntax_highlighting: Option<bool>,
+    #[serde(deserialize_with = "list")]
+    pub collapse: Vec<String>,
+    pub highlighter: Option<String>,
+    pub difftool: Option<String>,
+    // git config lowercases gitt.difftoolCapture
+    #[serde(deserialize_with = "boolean", alias = "difftoolcapture")]
+    pub difftool_capture: Option<bool>,
+    pub theme: Theme,
+    pub keys: Keymap,
+    columns: ColumnsConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ColumnsConfig {
+    subject: ColumnConfig,
+    author: ColumnConfig,
+    date: ColumnConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ColumnConfig {
+    #[serde(deserialize_with = "number")]
+    weight: Option<f64>,
+    #[serde(deserialize_with = "number")]
+    min: Option<f64>,
+    #[serde(deserialize_with = "number")]
+    max: Option<f64>,
+}
+
+impl ColumnConfig {
+    fn apply(&self, mut column: Column) -> Column {
+        column.weight = self.weight.unwrap_or(column.weight);
+        column.min = self.min.or(column.min);
+        column.max = self.max.or(column.max);
+        column
+    }
+}
+
+impl ColumnsConfig {
+    // Negative widths and a max under the min leave the commit list without a layout
+    fn check(&self, layout: &ColumnLayout) -> Result<(), String> {
+        let columns = [
+            ("subject", &self.subject, layout.subject),
+            ("author", &self.author, layout.author),
+            ("date", &self.date, layout.date),
+        ];
+        for (name, config, column) in columns {
+            let values = [
+                ("weight", config.weight),
+                ("min", config.min),
+                ("max", config.max),
+            ];
+            for (key, value) in values {
+                if let Some(value) = value.filter(|value| !(value.is_finite() && *value >= 0.0)) {
+                    return Err(format!(
+                        "Invalid gitt settings: columns.{}.{} has to be 0 or more, found {}",
+                        name, key, value
+                    ));
+                }
+            }
+            if let (Some(min), Some(max)) = (column.min, column.max) {
+                if min > max {
+                    return Err(format!(
+                        "Invalid gitt settings: columns.{}.min is {} but columns.{}.max is {}",
+                        name, min, name, max
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    // Reads $XDG_CONFIG_HOME/gitt/config.toml, or ~/.config/gitt/config.toml, and then the gitt.*
+    // keys of the repository's git config
+    pub fn load(repository: &git2::Repository) -> Result<Self, String> {
+        let mut settings = Table::new();
+        if let Some(path) = config_path() {
+            match std::fs::read_to_string(&path) {
+                Ok(text) => {
+                    let error = |e: toml::de::Error| format!("{}: {}", path.display(), e);
+                    // Parsed on its own first so errors point at a line of the file
+                    toml::from_str::<Config>(&text).map_err(error)?;
+                    settings = toml::from_str(&text).map_err(error)?;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Unable to read {}: {}", path.display(), e)),
+            }
+        }
+        let git_config = repository
+            .config()
+            .map_err(|e| format!("Unable to read git config: {}", e))?;
+        merge(&mut settings, git_settings(&git_config)?, false);
+        let config: Self = Value::Table(settings)
+            .try_into()
+            .map_err(|e| format!("Invalid gitt settings: {}", e))?;
+        config.columns.check(&config.column_layout())?;
+        Ok(config)
+    }
+
+    pub fn tick_rate(&self) -> Duration {
+        Duration::from_millis(self.tick_rate.unwrap_or(200))
+    }
+
+    pub fn diff_settings(&self) -> DiffSettings {
+        let mut settings = DiffSettings::default();
+        settings.context_lines = self.context.unwrap_or(settings.context_lines);
+        settings.algorithm = self.diff_algorithm.unwrap_or(settings.algorithm);
+        settings.whitespace = self.whitespace.unwrap_or(settings.whitespace);
+        settings.indent_heuristic = self.indent_heuristic.unwrap_or(settings.indent_heuristic);
+        settings.renames = self.renames.unwrap_or(settings.renames);
+        settings.copies = self.copies.unwrap_or(settings.copies);
+        settings.similarity_threshold = self.similarity.unwrap_or(settings.similarity_threshold);
+        settings
+    }
+
+    pub fn column_layout(&self) -> ColumnLayout {
+        let layout = ColumnLayout::default();
+        ColumnLayout {
+            subject: self.columns.subject.apply(layout.subject),
+            author: self.columns.author.apply(layout.author),
+            date: self.columns.date.apply(layout.date),
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|directory| !directory.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|directory| directory.join("gitt").join("config.toml"))
+}
+
+// gitt.tick-rate sets tick-rate and gitt.theme.added sets added in [theme]. Every value is a
+// string, the settings that take numbers and booleans parse them.
+fn git_settings(config: &git2::Config) -> Result<Table, String> {
+    let mut settings = Table::new();
+    let entries = config
+        .entries(Some("^gitt\\."))
+        .map_err(|e| format!("Unable to read git config: {}", e))?;
+    for entry in &entries {
+        let entry = entry.map_err(|e| format!("Unable to read git config: {}", e))?;
+        let (name, value) = match (entry.name(), entry.value()) {
+            (Some(name), Some(value)) => (name, value),
+            _ => continue,
+        };
+        let path: Vec<&str> = name.split('.').skip(1).collect();
+        let value = Value::String(value.to_string());
+        // Keys can be set multiple times, like gitt.collapse for several globs or gitt.keys.quit
+        // for several keys
+        let value =
+            if path.first() == Some(&"keys") || (path.len() == 1 && LISTS.contains(&path[0])) {
+                Value::Array(vec![value])
+            } else {
+                value
+            };
+        let setting = path.iter().rev().fold(value, |value, key| {
+            let mut table = Table::new();
+            table.insert(key.to_string(), value);
+            Value::Table(table)
+        });
+        // Checked on its own so the error can name the git config key
+        setting
+            .clone()
+            .try_into::<Config>()
+            .map_err(|e| format!("Invalid git config {}: {}", name, e))?;
+        if let Value::Table(setting) = setting {
+            merge(&mut settings, setting, true);
+        }
+    }
+    Ok(settings)
+}
+
+// Values in `overlay` replace the ones in `settings`, apart from lists which are appended to.
+// Every list is appended to when `append` is set, like actions bound more than once.
+fn merge(settings: &mut Table, overlay: Table, append: bool) {
+    for (key, value) in overlay {
+        match (settings.get_mut(&key), value) {
+            (Some(Value::Table(table)), Value::Table(overlay)) => merge(table, overlay, append),
+            (Some(Value::Array(list)), Value::Array(overlay))
+                if append || LISTS.contains(&key.as_str()) =>
+            {
+                list.extend(overlay)
+            }
+            (_, value) => {
+                settings.insert(key, value);
+            }
+        }
+    }
+}
+
+// Settings from git config are strings, so numbers and booleans are accepted as either
+trait Number: DeserializeOwned + FromStr {
+    // What the setting takes, for errors
+    const EXPECTED: &'static str;
+}
+
+impl Number for u16 {
+    const EXPECTED: &'static str = "a whole number of 0 or more";
+}
+
+impl Number for u32 {
+    const EXPECTED: &'static str = "a whole number of 0 or more";
+}
+
+impl Number for u64 {
+    const EXPECTED: &'static str = "a whole number of 0 or more";
+}
+
+impl Number for f64 {
+    const EXPECTED: &'static str = "a number";
+}
+
+fn number<'de, D: Deserializer<'de>, T: Number>(deserializer: D) -> Result<Option<T>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    let number = match &value {
+        Value::String(text) => text.trim().parse().ok(),
+        _ => value.clone().try_into().ok(),
+    };
+    number
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("expected {}, found {}", T::EXPECTED, value)))
+}
+
+// The same words git accepts for booleans
+fn boolean<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    match &value {
+        Value::Boolean(value) => Ok(Some(*value)),
+        Value::String(text) => match text.trim().to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(Some(true)),
+            "false" | "no" | "off" | "0" | "" => Ok(Some(false)),
+            _ => Err(D::Error::custom(format!(
+                "expected a boolean, found {}",
+                value
+            ))),
+        },
+        _ => Err(D::Error::custom(format!(
+            "expected a boolean, found {}",
+            value
+        ))),
+    }
+}
+
+// Events are polled for as long as the tick rate, so 0 would keep a core busy
+fn tick_rate<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    match number(deserializer)? {
+        Some(0) => Err(D::Error::custom(
+            "expected more than 0 milliseconds, found 0",
+        )),
+        rate => Ok(rate),
+    }
+}
+
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let text = String::deserialize(deserializer)?;
+    text.parse().map(Some).map_err(D::Error::custom)
+}
+
+fn list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        One(String),
+        Many(Vec<String>),
+    }
+    match List::deserialize(deserializer)? {
+        List::One(value) => Ok(vec![value]),
+        List::Many(values) => Ok(values),
+    }
+}
+
+// Formats chrono can't use would only fail once the commit list is drawn
+fn date_format<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let format = String::deserialize(deserializer)?;
+    if chrono::format::StrftimeItems::new(&format).any(|item| item == chrono::format::Item::Error) {
+        return Err(D::Error::custom(format!("invalid date format {}", format)));
+    }
+    Ok(Some(format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(text: &str) -> Table {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn overlay_replaces_values_and_appends_lists() {
+        let mut settings = table(
+            "args = ['--no-renames']\ncontext = 3\n[theme]\nadded = 'green'\nremoved = 'red'\n\
+             [keys]\nquit = ['q']",
+        );
+        merge(
+            &mut settings,
+            table("args = ['-C']\ncontext = 5\n[theme]\nadded = 'blue'\n[keys]\nquit = ['Q']"),
+            false,
+        );
+        assert_eq!(
+            settings,
+            table(
+                "args = ['--no-renames', '-C']\ncontext = 5\n\
+                 [theme]\nadded = 'blue'\nremoved = 'red'\n[keys]\nquit = ['Q']"
+            )
+        );
+    }
+
+    #[test]
+    fn append_extends_every_list() {
+        let mut settings = table("[keys]\nquit = ['q']");
+        merge(&mut settings, table("[keys]\nquit = ['Q']"), true);
+        assert_eq!(settings, table("[keys]\nquit = ['q', 'Q']"));
+    }
+
+    #[test]
+    fn reads_numbers_and_booleans_from_strings() {
+        let config: Config =
+            toml::from_str("tick-rate = '50'\nrenames = 'no'\ncopies = true").unwrap();
+        assert_eq!(config.tick_rate, Some(50));
+        assert_eq!(config.renames, Some(false));
+        assert_eq!(config.copies, Some(true));
+    }
+
+    #[test]
+    fn errors_name_the_expected_type_and_value() {
+        let error = |text| toml::from_str::<Config>(text).unwrap_err().to_string();
+        assert!(
+            error("tick-rate = -5").starts_with("expected a whole number of 0 or more, found -5")
+        );
+        assert!(error("tick-rate = '0'").starts_with("expected more than 0 milliseconds, found 0"));
+        assert!(error("renames = 'maybe'").starts_with("expected a boolean, found \"maybe\""));
+    }
+
+    #[test]
+    fn checks_column_widths() {
+        let check = |text| {
+            let config: Config = toml::from_str(text).unwrap();
+            config.columns.check(&config.column_layout())
+        };
+        assert!(check("[columns]\nauthor = { weight = 20, min = 10 }").is_ok());
+        assert_eq!(
+            check("[columns]\ndate = { max = -1 }"),
+            Err(
+                "Invalid gitt settings: columns.date.max has to be 0 or more, found -1".to_string()
+            )
+        );
+        assert_eq!(
+            check("[columns]\ndate = { min = 20 }"),
+            Err(
+                "Invalid gitt settings: columns.date.min is 20 but columns.date.max is 16"
+                    .to_string()
+            )
+        );
+    }
+}
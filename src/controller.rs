@@ -1,9 +1,13 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver, RecvError};
+use std::sync::Arc;
 use std::time::Duration;
 
 use crossterm::event::{poll, read, Event as CrosstermEvent, KeyCode, KeyEvent, KeyModifiers};
 
-use crate::model::{AppModel, AppState};
+use crate::clipboard;
+use crate::keymap::Keymap;
+use crate::model::{AppModel, AppState, Yank};
 
 pub enum Event<I> {
     Input(I),
@@ -26,11 +30,26 @@ impl Event<KeyEvent> {
     }
 }
 
-pub fn event_receiver(tick_rate: Duration) -> Receiver<Event<KeyEvent>> {
+// Lets another program read the terminal while gitt is suspended
+#[derive(Clone, Default)]
+pub struct Suspension {
+    requested: Arc<AtomicBool>,
+    // Set by the event thread once it has stopped reading the terminal
+    acknowledged: Arc<AtomicBool>,
+}
+
+pub fn event_receiver(tick_rate: Duration, suspension: Suspension) -> Receiver<Event<KeyEvent>> {
     let (tx, rx) = channel();
     std::thread::spawn(move || {
         let mut last_tick = std::time::Instant::now();
         loop {
+            if suspension.requested.load(Ordering::SeqCst) {
+                suspension.acknowledged.store(true, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            suspension.acknowledged.store(false, Ordering::SeqCst);
+
             let timeout = tick_rate
                 .checked_sub(last_tick.elapsed())
                 .unwrap_or_else(|| Duration::from_secs(0));
@@ -41,10 +60,8 @@ pub fn event_receiver(tick_rate: Duration) -> Receiver<Event<KeyEvent>> {
                 _ => {}
             }
 
-            if last_tick.elapsed() >= tick_rate {
-                if tx.send(Event::Tick).is_ok() {
-                    last_tick = std::time::Instant::now();
-                }
+            if last_tick.elapsed() >= tick_rate && tx.send(Event::Tick).is_ok() {
+                last_tick = std::time::Instant::now();
             }
         }
     });
@@ -53,20 +70,58 @@ pub fn event_receiver(tick_rate: Duration) -> Receiver<Event<KeyEvent>> {
 
 pub struct EventHandler {
     receiver: Receiver<Event<KeyEvent>>,
+    // First key of a two key sequence like ]c
+    pending: Option<char>,
+    suspension: Suspension,
+    keymap: Keymap,
 }
 
 impl EventHandler {
-    pub fn new(tick_rate: Duration) -> Self {
+    pub fn new(tick_rate: Duration, keymap: Keymap) -> Self {
+        let suspension = Suspension::default();
         Self {
-            receiver: event_receiver(tick_rate),
+            receiver: event_receiver(tick_rate, suspension.clone()),
+            pending: None,
+            suspension,
+            keymap,
         }
     }
 
+    // Stops reading the terminal, waiting for a read that is already under way to finish
+    pub fn suspend(&self) {
+        self.suspension.requested.store(true, Ordering::SeqCst);
+        while !self.suspension.acknowledged.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    pub fn resume(&self) {
+        self.suspension.requested.store(false, Ordering::SeqCst);
+    }
+
     pub fn update_model(&mut self, model: &mut AppModel) -> Result<(), RecvError> {
         loop {
             match self.receiver.recv()? {
                 Event::Input(event) => {
-                    if model.app_state == AppState::Commits {
+                    model.clear_status();
+                    let pending = self.pending.take();
+                    // Keys bound in the config are matched as the default keys of their actions,
+                    // the second key of a sequence keeps its meaning
+                    let event = match pending {
+                        Some(_) => event,
+                        None => match self.keymap.translate(event) {
+                            Some(event) => event,
+                            None => break,
+                        },
+                    };
+                    if pending == Some('y') {
+                        if let KeyCode::Char(key) = event.code {
+                            yank(model, key);
+                        }
+                    } else if event.code == KeyCode::Char('y') {
+                        // Copying works the same in every pane
+                        self.pending = Some('y');
+                    } else if model.app_state == AppState::Commits {
                         match event {
                             KeyEvent {
                                 code: KeyCode::Char('q'),
@@ -93,6 +148,54 @@ impl EventHandler {
                             } => {
                                 model.go_to_last_revision();
                             }
+                            KeyEvent {
+                                code: KeyCode::Char('m'),
+                                ..
+                            } => {
+                                model.toggle_mark();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('x'),
+                                ..
+                            } => {
+                                model.swap_compared();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('E'),
+                                ..
+                            } => {
+                                model.export_patches();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('D'),
+                                ..
+                            } => {
+                                model.run_diff_tool();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('t'),
+                                ..
+                            } => {
+                                model.open_tree();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char(' '),
+                                ..
+                            } if model.is_picking() => {
+                                model.toggle_pick();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Enter,
+                                ..
+                            } if model.is_picking() => {
+                                model.finish_picking();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('L'),
+                                ..
+                            } => {
+                                model.clear_line_range_filters();
+                            }
                             KeyEvent {
                                 code: KeyCode::Down,
                                 ..
@@ -116,6 +219,36 @@ impl EventHandler {
                         }
                     } else if model.app_state == AppState::Details {
                         match event {
+                            KeyEvent {
+                                code: KeyCode::Char(prefix @ ']'),
+                                ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char(prefix @ '['),
+                                ..
+                            } => {
+                                self.pending = Some(prefix);
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('c'),
+                                ..
+                            } if pending.is_some() => {
+                                if pending == Some(']') {
+                                    model.next_hunk();
+                                } else {
+                                    model.previous_hunk();
+                                }
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('f'),
+                                modifiers: KeyModifiers::NONE,
+                            } if pending.is_some() => {
+                                if pending == Some(']') {
+                                    model.next_file();
+                                } else {
+                                    model.previous_file();
+                                }
+                            }
                             KeyEvent {
                                 code: KeyCode::Char('q'),
                                 ..
@@ -126,7 +259,12 @@ impl EventHandler {
                                 code: KeyCode::Tab, ..
                             } => {
                                 // TODO: statemachine for app state progression
-                                model.app_state = AppState::Commits;
+                                // The tree browser takes the place of the file list while open
+                                model.app_state = if model.tree().is_some() {
+                                    AppState::Tree
+                                } else {
+                                    AppState::Files
+                                };
                             }
                             // Details navigation
                             KeyEvent {
@@ -161,6 +299,159 @@ impl EventHandler {
                                 model.decrement_diff_line();
                             }
 
+                            KeyEvent {
+                                code: KeyCode::Char('w'),
+                                ..
+                            } => {
+                                model.set_word_diff(model.word_diff().next());
+                            }
+
+                            KeyEvent {
+                                code: KeyCode::Char('+'),
+                                ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char('='),
+                                ..
+                            } => {
+                                let mut settings = model.diff_settings().clone();
+                                settings.context_lines += 1;
+                                model.set_diff_settings(settings);
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('-'),
+                                ..
+                            } => {
+                                let mut settings = model.diff_settings().clone();
+                                settings.context_lines = settings.context_lines.saturating_sub(1);
+                                model.set_diff_settings(settings);
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('W'),
+                                ..
+                            } => {
+                                let mut settings = model.diff_settings().clone();
+                                settings.whitespace = settings.whitespace.next();
+                                model.set_diff_settings(settings);
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('a'),
+                                ..
+                            } => {
+                                let mut settings = model.diff_settings().clone();
+                                settings.algorithm = settings.algorithm.next();
+                                model.set_diff_settings(settings);
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('i'),
+                                ..
+                            } => {
+                                let mut settings = model.diff_settings().clone();
+                                settings.indent_heuristic = !settings.indent_heuristic;
+                                model.set_diff_settings(settings);
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('R'),
+                                ..
+                            } => {
+                                let mut settings = model.diff_settings().clone();
+                                settings.renames = !settings.renames;
+                                model.set_diff_settings(settings);
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('C'),
+                                ..
+                            } => {
+                                let mut settings = model.diff_settings().clone();
+                                settings.copies = !settings.copies;
+                                model.set_diff_settings(settings);
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('z'),
+                                ..
+                            } => {
+                                model.toggle_file_collapsed();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('S'),
+                                ..
+                            } => {
+                                model.set_syntax_highlighting(!model.syntax_highlighting());
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('H'),
+                                ..
+                            } => {
+                                model.toggle_highlighting();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('v'),
+                                ..
+                            } => {
+                                if model.file_view().is_some() {
+                                    model.close_file_view();
+                                } else {
+                                    model.open_file_view();
+                                }
+                            }
+                            KeyEvent {
+                                code: KeyCode::Esc, ..
+                            } => {
+                                if model.line_range_marked() {
+                                    model.clear_line_range_mark();
+                                } else if model.external_output().is_some() {
+                                    model.close_external_output();
+                                } else if model.blame().is_some() {
+                                    model.close_blame();
+                                } else {
+                                    model.close_file_view();
+                                }
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('L'),
+                                ..
+                            } => {
+                                model.mark_line_range();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('e'),
+                                ..
+                            } => {
+                                model.edit_line();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('D'),
+                                ..
+                            } => {
+                                model.run_diff_tool();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('p'),
+                                ..
+                            } => {
+                                if model.blame().is_some() {
+                                    model.blame_parent();
+                                } else {
+                                    model.toggle_file_view_parent();
+                                }
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('b'),
+                                modifiers: KeyModifiers::NONE,
+                            } => {
+                                if model.blame().is_some() {
+                                    model.close_blame();
+                                } else {
+                                    model.open_blame();
+                                }
+                            }
+                            KeyEvent {
+                                code: KeyCode::Enter,
+                                ..
+                            } => {
+                                model.select_blamed_commit();
+                            }
+
                             KeyEvent {
                                 code: KeyCode::PageDown,
                                 ..
@@ -175,6 +466,22 @@ impl EventHandler {
                                 }
                             }
 
+                            KeyEvent {
+                                code: KeyCode::Char('d'),
+                                modifiers: KeyModifiers::CONTROL,
+                            } => {
+                                let (_, window_length, _) = model.diff_line_scroll();
+                                model.scroll_diff_by((window_length / 2) as isize);
+                            }
+
+                            KeyEvent {
+                                code: KeyCode::Char('u'),
+                                modifiers: KeyModifiers::CONTROL,
+                            } => {
+                                let (_, window_length, _) = model.diff_line_scroll();
+                                model.scroll_diff_by(-((window_length / 2) as isize));
+                            }
+
                             KeyEvent {
                                 code: KeyCode::PageUp,
                                 ..
@@ -190,16 +497,202 @@ impl EventHandler {
                             }
                             _ => {}
                         }
+                    } else if model.app_state == AppState::Files {
+                        match event {
+                            KeyEvent {
+                                code: KeyCode::Char('q'),
+                                ..
+                            } => {
+                                model.app_state = AppState::Finished;
+                            }
+                            KeyEvent {
+                                code: KeyCode::Tab, ..
+                            } => {
+                                // TODO: statemachine for app state progression
+                                model.app_state = AppState::Commits;
+                            }
+                            KeyEvent {
+                                code: KeyCode::Enter,
+                                ..
+                            } => {
+                                model.app_state = AppState::Details;
+                            }
+                            // File navigation
+                            KeyEvent {
+                                code: KeyCode::Char('g'),
+                                ..
+                            } => {
+                                model.select_file(0);
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('G'),
+                                ..
+                            } => {
+                                model.select_file(model.diff_files().len().saturating_sub(1));
+                            }
+                            KeyEvent {
+                                code: KeyCode::Down,
+                                ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char('j'),
+                                ..
+                            } => {
+                                model.increment_file();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Up, ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char('k'),
+                                ..
+                            } => {
+                                model.decrement_file();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('z'),
+                                ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char(' '),
+                                ..
+                            } => {
+                                model.toggle_file_collapsed();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('v'),
+                                ..
+                            } => {
+                                model.open_file_view();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('b'),
+                                ..
+                            } => {
+                                model.open_blame();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('D'),
+                                ..
+                            } => {
+                                model.run_diff_tool();
+                            }
+                            _ => {}
+                        }
+                    } else if model.app_state == AppState::Tree {
+                        match event {
+                            KeyEvent {
+                                code: KeyCode::Char('q'),
+                                ..
+                            } => {
+                                model.app_state = AppState::Finished;
+                            }
+                            KeyEvent {
+                                code: KeyCode::Tab, ..
+                            } => {
+                                // TODO: statemachine for app state progression
+                                model.app_state = AppState::Commits;
+                            }
+                            KeyEvent {
+                                code: KeyCode::Esc, ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char('t'),
+                                ..
+                            } => {
+                                model.close_tree();
+                            }
+                            // Tree navigation
+                            KeyEvent {
+                                code: KeyCode::Char('g'),
+                                ..
+                            } => {
+                                model.go_to_first_tree_row();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Char('G'),
+                                ..
+                            } => {
+                                model.go_to_last_tree_row();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Down,
+                                ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char('j'),
+                                ..
+                            } => {
+                                model.increment_tree_row();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Up, ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char('k'),
+                                ..
+                            } => {
+                                model.decrement_tree_row();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Enter,
+                                ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Right,
+                                ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char('l'),
+                                ..
+                            } => {
+                                model.open_tree_row();
+                            }
+                            KeyEvent {
+                                code: KeyCode::Left,
+                                ..
+                            }
+                            | KeyEvent {
+                                code: KeyCode::Char('h'),
+                                ..
+                            } => {
+                                model.collapse_tree_row();
+                            }
+                            _ => {}
+                        }
                     }
                 }
                 Event::Resize(_rows) => {} // Let the drawing code handle this
                 Event::Failure => {
                     model.app_state = crate::model::AppState::Finished;
                 }
-                Event::Tick => continue,
+                Event::Tick => {
+                    if !model.tick() {
+                        continue;
+                    }
+                }
             };
             break;
         }
         Ok(())
     }
 }
+
+fn yank(model: &mut AppModel, key: char) {
+    let yank = match key {
+        'y' => Yank::Id,
+        'h' => Yank::ShortId,
+        's' => Yank::Subject,
+        'r' => Yank::Reference,
+        'f' => Yank::Path,
+        _ => return,
+    };
+    let status = match model.yank_text(yank) {
+        Some(text) => match clipboard::copy(&text) {
+            Ok(()) => format!("Copied {}", text),
+            Err(e) => format!("Unable to copy: {}", e),
+        },
+        None => "Nothing to copy".to_string(),
+    };
+    model.set_status(status);
+}
@@ -0,0 +1,174 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use git2::{DiffFindOptions, DiffOptions};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Whitespace {
+    Show,
+    IgnoreAtEol,
+    IgnoreChange,
+    IgnoreAll,
+}
+
+impl Whitespace {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Show => Self::IgnoreAtEol,
+            Self::IgnoreAtEol => Self::IgnoreChange,
+            Self::IgnoreChange => Self::IgnoreAll,
+            Self::IgnoreAll => Self::Show,
+        }
+    }
+}
+
+// Named after the git diff flags
+impl FromStr for Whitespace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "show" => Ok(Self::Show),
+            "ignore-space-at-eol" => Ok(Self::IgnoreAtEol),
+            "ignore-space-change" => Ok(Self::IgnoreChange),
+            "ignore-all-space" => Ok(Self::IgnoreAll),
+            _ => Err(format!("Unknown whitespace setting: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    Myers,
+    Minimal,
+    Patience,
+}
+
+impl DiffAlgorithm {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Myers => Self::Minimal,
+            Self::Minimal => Self::Patience,
+            Self::Patience => Self::Myers,
+        }
+    }
+}
+
+impl FromStr for DiffAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "myers" | "default" => Ok(Self::Myers),
+            "minimal" => Ok(Self::Minimal),
+            "patience" => Ok(Self::Patience),
+            "histogram" => Err("libgit2 has no histogram diff, use patience instead".to_string()),
+            _ => Err(format!("Unknown diff algorithm: {}", s)),
+        }
+    }
+}
+
+impl Display for DiffAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Myers => write!(f, "myers"),
+            Self::Minimal => write!(f, "minimal"),
+            Self::Patience => write!(f, "patience"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffSettings {
+    pub context_lines: u32,
+    pub whitespace: Whitespace,
+    pub algorithm: DiffAlgorithm,
+    pub indent_heuristic: bool,
+    pub renames: bool,
+    pub copies: bool,
+    // Percentage of a file that has to be unchanged for it to count as a rename or copy
+    pub similarity_threshold: u16,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        Self {
+            context_lines: 3,
+            whitespace: Whitespace::Show,
+            algorithm: DiffAlgorithm::Myers,
+            indent_heuristic: true,
+            renames: true,
+            copies: false,
+            similarity_threshold: 50,
+        }
+    }
+}
+
+impl DiffSettings {
+    pub fn options(&self) -> DiffOptions {
+        let mut options = DiffOptions::new();
+        options
+            .context_lines(self.context_lines)
+            .ignore_whitespace_eol(self.whitespace == Whitespace::IgnoreAtEol)
+            .ignore_whitespace_change(self.whitespace == Whitespace::IgnoreChange)
+            .ignore_whitespace(self.whitespace == Whitespace::IgnoreAll)
+            .minimal(self.algorithm == DiffAlgorithm::Minimal)
+            .patience(self.algorithm == DiffAlgorithm::Patience)
+            .indent_heuristic(self.indent_heuristic);
+        options
+    }
+
+    // Returns None when neither renames nor copies are being looked for
+    pub fn find_options(&self) -> Option<DiffFindOptions> {
+        if !self.renames && !self.copies {
+            return None;
+        }
+        let mut options = DiffFindOptions::new();
+        options
+            .renames(self.renames)
+            .copies(self.copies)
+            .rename_threshold(self.similarity_threshold)
+            .copy_threshold(self.similarity_threshold);
+        Some(options)
+    }
+}
+
+// Summarises the settings with the equivalent git diff flags
+impl Display for DiffSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "-U{} --diff-algorithm={}",
+            self.context_lines, self.algorithm
+        )?;
+        match self.whitespace {
+            Whitespace::Show => {}
+            Whitespace::IgnoreAtEol => write!(f, " --ignore-space-at-eol")?,
+            Whitespace::IgnoreChange => write!(f, " --ignore-space-change")?,
+            Whitespace::IgnoreAll => write!(f, " --ignore-all-space")?,
+        }
+        if !self.indent_heuristic {
+            write!(f, " --no-indent-heuristic")?;
+        }
+        if self.copies {
+            write!(f, " -C{}%", self.similarity_threshold)?;
+        } else if self.renames {
+            write!(f, " -M{}%", self.similarity_threshold)?;
+        } else {
+            write!(f, " --no-renames")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_algorithms_libgit2_has() {
+        assert_eq!("default".parse(), Ok(DiffAlgorithm::Myers));
+        assert_eq!("patience".parse(), Ok(DiffAlgorithm::Patience));
+        assert!("histogram".parse::<DiffAlgorithm>().is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossterm::tty::IsTty;
+
+// How long a program whose output is shown in gitt gets, gitt waits for it without redrawing
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A program run for the user, like an editor. Programs drawing their own interface get the
+// terminal while gitt's is suspended, others have their output captured to be shown in gitt.
+pub struct ExternalCommand {
+    // The name used in messages, like the editor or difftool name
+    pub name: String,
+    // Run by the shell like git runs $EDITOR, so it can contain arguments of its own
+    pub script: String,
+    // The program is run once for each of these, one after another
+    pub runs: Vec<Run>,
+    pub capture: bool,
+    // Temporary files to remove once the program exits
+    pub cleanup: Vec<PathBuf>,
+}
+
+// The positional arguments and environment of one run of a program
+pub struct Run {
+    pub args: Vec<OsString>,
+    pub env: Vec<(&'static str, OsString)>,
+}
+
+// How a configured diff program wants to be called
+pub enum DiffTool {
+    // diff.external, called with the path, the old file, its id and mode and then the new file,
+    // its id and mode, like GIT_EXTERNAL_DIFF
+    External(String),
+    // difftool.<name>.cmd, a shell command reading the files from $LOCAL and $REMOTE
+    Tool {
+        name: String,
+        cmd: String,
+        capture: bool,
+    },
+}
+
+// The file pair given to a diff program, /dev/null for a side that doesn't exist
+pub struct DiffFiles {
+    pub path: PathBuf,
+    pub old_file: PathBuf,
+    pub old_id: String,
+    pub old_mode: String,
+    pub new_file: PathBuf,
+    pub new_id: String,
+    pub new_mode: String,
+}
+
+impl DiffTool {
+    // gitt.difftool picks a difftool over diff.tool, then diff.external is used. Difftools are
+    // usually interactive so they get the terminal unless gitt.difftool-capture is set, while
+    // diff.external output is meant for a pager and is always captured. The tools git knows
+    // without a cmd, like vimdiff, each need their own arguments, so only tools with a cmd can
+    // be run.
+    pub fn from_config(
+        config: &git2::Config,
+        tool: Option<String>,
+        capture: bool,
+    ) -> Result<Self, String> {
+        let tool = tool
+            .or_else(|| config.get_string("diff.tool").ok())
+            .filter(|tool| !tool.is_empty());
+        if let Some(name) = tool {
+            let key = format!("difftool.{}.cmd", name);
+            return match config.get_string(&key) {
+                Ok(cmd) if !cmd.trim().is_empty() => Ok(DiffTool::Tool { name, cmd, capture }),
+                _ => Err(format!("Set {} to the command that runs {}", key, name)),
+            };
+        }
+        config
+            .get_string("diff.external")
+            .ok()
+            .filter(|external| !external.is_empty())
+            .map(DiffTool::External)
+            .ok_or_else(|| "No diff program configured, set diff.tool or diff.external".to_string())
+    }
+
+    pub fn command(self, files: Vec<DiffFiles>, cleanup: Vec<PathBuf>) -> ExternalCommand {
+        match self {
+            DiffTool::External(external) => ExternalCommand {
+                script: format!("{} \"$@\"", external),
+                name: external,
+                runs: files
+                    .into_iter()
+                    .map(|files| Run {
+                        args: vec![
+                            files.path.into(),
+                            files.old_file.into(),
+                            files.old_id.into(),
+                            files.old_mode.into(),
+                            files.new_file.into(),
+                            files.new_id.into(),
+                            files.new_mode.into(),
+                        ],
+                        env: Vec::new(),
+                    })
+                    .collect(),
+                capture: true,
+                cleanup,
+            },
+            DiffTool::Tool { name, cmd, capture } => ExternalCommand {
+                name,
+                script: cmd,
+                runs: files
+                    .into_iter()
+                    .map(|files| Run {
+                        args: Vec::new(),
+                        env: vec![
+                            ("LOCAL", files.old_file.into()),
+                            ("REMOTE", files.new_file.into()),
+                            ("MERGED", files.path.clone().into()),
+                            ("BASE", files.path.into()),
+                        ],
+                    })
+                    .collect(),
+                capture,
+                cleanup,
+            },
+        }
+    }
+}
+
+impl ExternalCommand {
+    // Opens `path` at `line` with $VISUAL or $EDITOR, falling back to vi like git does
+    pub fn editor(path: PathBuf, line: usize, cleanup: Vec<PathBuf>) -> Self {
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .ok()
+            .filter(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| "vi".to_string());
+        Self {
+            script: format!("{} \"$@\"", editor),
+            name: editor,
+            runs: vec![Run {
+                args: vec![format!("+{}", line).into(), path.into()],
+                env: Vec::new(),
+            }],
+            capture: false,
+            cleanup,
+        }
+    }
+
+    // Runs the program in `directory`, returning what it printed when the output is captured
+    pub fn run(self, directory: &Path) -> Result<Option<String>, String> {
+        let mut output = String::new();
+        let mut result = Ok(());
+        let started = Instant::now();
+        for run in &self.runs {
+            let mut command = Command::new("sh");
+            command
+                .current_dir(directory)
+                .arg("-c")
+                .arg(&self.script)
+                .arg(&self.name)
+                .args(&run.args)
+                .envs(run.env.iter().map(|(key, value)| (key, value)));
+            if self.capture {
+                command
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+                let remaining = CAPTURE_TIMEOUT.saturating_sub(started.elapsed());
+                match command
+                    .spawn()
+                    .and_then(|child| wait_with_timeout(child, remaining))
+                {
+                    Ok(None) => {
+                        result = Err(format!(
+                            "{} was stopped after {} seconds",
+                            self.name,
+                            CAPTURE_TIMEOUT.as_secs()
+                        ));
+                        break;
+                    }
+                    Ok(Some(captured)) => {
+                        output.push_str(&String::from_utf8_lossy(&captured.stdout));
+                        // Diff programs exit with 1 when the files differ, so the exit status
+                        // only counts when nothing was printed
+                        if captured.stdout.is_empty() && !captured.status.success() {
+                            let message = String::from_utf8_lossy(&captured.stderr);
+                            result = Err(format!(
+                                "{} exited with {}: {}",
+                                self.name,
+                                captured.status,
+                                message.lines().next().unwrap_or_default()
+                            ));
+                        }
+                    }
+                    Err(error) => result = Err(format!("Unable to run {}: {}", self.name, error)),
+                }
+                continue;
+            }
+            // When gitt's output is captured, like in $(gitt --pick), the program still needs the
+            // terminal
+            if !std::io::stdout().is_tty() {
+                if let Ok(tty) = std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+                    command.stdout(Stdio::from(tty));
+                }
+            }
+            match command.status() {
+                Ok(status) if status.success() => {}
+                Ok(status) => result = Err(format!("{} exited with {}", self.name, status)),
+                Err(error) => result = Err(format!("Unable to run {}: {}", self.name, error)),
+            }
+        }
+        for path in &self.cleanup {
+            remove_temporary(path);
+        }
+        match result {
+            // Output from the files that worked is still worth showing
+            Err(error) if !self.capture || output.is_empty() => Err(error),
+            _ => Ok(Some(output).filter(|_| self.capture)),
+        }
+    }
+}
+
+// Creates a directory only the user can use for temporary files. Directories that already exist
+// are never used, so nobody else can have put files in it or be watching it.
+pub fn temporary_directory() -> std::io::Result<PathBuf> {
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    for _ in 0..100 {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or_default();
+        let directory = std::env::temp_dir().join(format!(
+            "gitt-{}-{}-{:08x}",
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::SeqCst),
+            nanos
+        ));
+        match builder.create(&directory) {
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            result => return result.map(|_| directory),
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        "unable to create a new temporary directory",
+    ))
+}
+
+// Removes a file written to a directory from temporary_directory, along with the directory
+pub fn remove_temporary(path: &Path) {
+    let _ = std::fs::remove_file(path);
+    if let Some(directory) = path.parent() {
+        let _ = std::fs::remove_dir(directory);
+    }
+}
+
+// Pipes `input` through a filter like a diff highlighter and returns what it printed. The filter
+// is killed if it takes longer than `timeout`, so a stuck filter can't hang the interface.
+pub fn filter(
+    command: &str,
+    input: String,
+    columns: usize,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("COLUMNS", columns.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| format!("Unable to run {}: {}", command, error))?;
+    let mut stdin = child.stdin.take().expect("stdin was not piped");
+    // Writing and reading on their own threads keeps a filter that fills the pipe before reading
+    // all of its input from blocking either side
+    std::thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let output = wait_with_timeout(child, timeout)
+        .map_err(|error| error.to_string())?
+        .ok_or_else(|| format!("{} took too long", command))?;
+    if !output.status.success() {
+        return Err(format!("{} exited with {}", command, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Collects what a program prints on other threads so it can be killed once `timeout` has passed,
+// returning None if it had to be
+fn wait_with_timeout(mut child: Child, timeout: Duration) -> std::io::Result<Option<Output>> {
+    let deadline = Instant::now() + timeout;
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let result = match pipe {
+                Some(mut pipe) => pipe.read_to_end(&mut output).map(|_| output),
+                None => Ok(output),
+            };
+            let _ = sender.send(result);
+        });
+        receiver
+    };
+    let stdout = read(
+        child
+            .stdout
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+    );
+    let stderr = read(
+        child
+            .stderr
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+    );
+    let mut collect = |receiver: std::sync::mpsc::Receiver<std::io::Result<Vec<u8>>>| match receiver
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+    {
+        Ok(output) => output.map(Some),
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Ok(None)
+        }
+    };
+    let (stdout, stderr) = match (collect(stdout)?, collect(stderr)?) {
+        (Some(stdout), Some(stderr)) => (stdout, stderr),
+        _ => return Ok(None),
+    };
+    let status = child.wait()?;
+    Ok(Some(Output {
+        status,
+        stdout,
+        stderr,
+    }))
+}
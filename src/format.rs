@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use chrono::offset::TimeZone;
+use git2::{Commit, Oid, Repository};
+
+use crate::graph::{self, Graph};
+
+// Formats with a strftime format like the date-format setting, RFC 3339 without one
+pub fn format_time(time: &git2::Time, format: Option<&str>) -> String {
+    let tz = chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
+        .expect("timezone offset was too big");
+    let dt = tz.timestamp(time.seconds(), 0);
+    match format {
+        Some(format) => dt.format(format).to_string(),
+        None => dt.to_rfc3339(),
+    }
+}
+
+// The names of the refs pointing at each commit, in the style of git log --decorate
+pub struct Decorations {
+    names: HashMap<Oid, Vec<String>>,
+}
+
+impl Decorations {
+    pub fn new(repository: &Repository) -> Result<Self, git2::Error> {
+        let mut names: HashMap<Oid, Vec<String>> = HashMap::new();
+        let head = repository.head().ok();
+        let head_detached = repository.head_detached().unwrap_or(false);
+        if let (Some(head), true) = (&head, head_detached) {
+            if let Ok(commit) = head.peel_to_commit() {
+                names
+                    .entry(commit.id())
+                    .or_default()
+                    .push("HEAD".to_string());
+            }
+        }
+        for reference in repository.references()? {
+            let reference = reference?;
+            // Symbolic refs like origin/HEAD only repeat the ref they point at
+            if reference.target().is_none() {
+                continue;
+            }
+            let commit = match reference.peel_to_commit() {
+                Ok(commit) => commit,
+                Err(_) => continue,
+            };
+            let shorthand = reference.shorthand().unwrap_or("INVALID UTF8 IN REF");
+            let is_head =
+                !head_detached && head.as_ref().and_then(|head| head.name()) == reference.name();
+            let name = if is_head {
+                format!("HEAD -> {}", shorthand)
+            } else if reference.is_tag() {
+                format!("tag: {}", shorthand)
+            } else {
+                shorthand.to_string()
+            };
+            let commit_names = names.entry(commit.id()).or_default();
+            if is_head {
+                commit_names.insert(0, name);
+            } else {
+                commit_names.push(name);
+            }
+        }
+        Ok(Self { names })
+    }
+
+    pub fn get(&self, id: Oid) -> &[String] {
+        self.names.get(&id).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+// Expands git log --format style placeholders:
+//   %H %h   the full and short SHA1
+//   %P %p   the full and short SHA1s of the parents
+//   %an %ae %ad   the author's name, email and date, %cn %ce %cd for the committer
+//   %s %b   the subject and the body of the message
+//   %d %D   the refs pointing at the commit, with and without the surrounding " (...)"
+//   %n %%   a newline and a percent sign
+// Anything else is copied as it is.
+pub fn format_commit(template: &str, commit: &git2::Commit, refs: &[String]) -> String {
+    let mut formatted = String::new();
+    let mut rest = template;
+    while let Some(index) = rest.find('%') {
+        formatted.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+        let (expansion, length) = match expand(rest, commit, refs) {
+            Some(expanded) => expanded,
+            None => ("%".to_string(), 0),
+        };
+        formatted.push_str(&expansion);
+        rest = &rest[length..];
+    }
+    formatted.push_str(rest);
+    formatted
+}
+
+// Expands the placeholder at the start of `rest`, returning the expansion and how much of `rest`
+// it used
+fn expand(rest: &str, commit: &git2::Commit, refs: &[String]) -> Option<(String, usize)> {
+    let mut chars = rest.chars();
+    let expansion = match chars.next()? {
+        'H' => commit.id().to_string(),
+        'h' => crate::model::short_id(commit),
+        'P' => parents(commit, |parent| parent.id().to_string()),
+        'p' => parents(commit, crate::model::short_id),
+        's' => commit.summary().unwrap_or_default().to_string(),
+        'b' => body(commit),
+        'd' if refs.is_empty() => String::new(),
+        'd' => format!(" ({})", refs.join(", ")),
+        'D' => refs.join(", "),
+        'n' => "\n".to_string(),
+        '%' => "%".to_string(),
+        kind @ ('a' | 'c') => {
+            let signature = if kind == 'a' {
+                commit.author()
+            } else {
+                commit.committer()
+            };
+            let expansion = match chars.next()? {
+                'n' => signature.name().unwrap_or_default().to_string(),
+                'e' => signature.email().unwrap_or_default().to_string(),
+                'd' => format_time(&signature.when(), None),
+                _ => return None,
+            };
+            return Some((expansion, 2));
+        }
+        _ => return None,
+    };
+    Some((expansion, 1))
+}
+
+fn parents(commit: &git2::Commit, format: impl Fn(&git2::Commit) -> String) -> String {
+    commit
+        .parents()
+        .map(|parent| format(&parent))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Everything after the subject's paragraph, like git's %b
+fn body(commit: &git2::Commit) -> String {
+    let message = commit.message().unwrap_or_default();
+    match message.trim_start().find("\n\n") {
+        Some(index) => message.trim_start()[index + 2..].to_string(),
+        None => String::new(),
+    }
+}
+
+// Writes a line for each commit instead of starting the interface, optionally after the graph
+pub fn print_commits<'a>(
+    output: &mut impl Write,
+    repository: &Repository,
+    commits: impl Iterator<Item = Commit<'a>>,
+    template: &str,
+    show_graph: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let decorations = Decorations::new(repository)?;
+    if !show_graph {
+        for commit in commits {
+            let refs = decorations.get(commit.id());
+            writeln!(output, "{}", format_commit(template, &commit, refs))?;
+        }
+        return Ok(());
+    }
+    // The graph needs every listed commit to know which parents it will reach
+    let commits: Vec<_> = commits.collect();
+    let mut graph = Graph::new();
+    for (commit, parents) in commits.iter().zip(graph::listed_parents(&commits)) {
+        let row = graph.row(commit.id(), &parents);
+        let refs = decorations.get(commit.id());
+        let formatted = format_commit(template, commit, refs);
+        // Lines after the first only show the lanes carrying on, like git log --graph
+        let continuation = graph.passing(row.cells.len()).to_string();
+        for (index, line) in formatted.split('\n').enumerate() {
+            let prefix = if index == 0 {
+                row.to_string()
+            } else {
+                continuation.clone()
+            };
+            writeln!(output, "{}{}", prefix, line)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_placeholders() {
+        let directory =
+            std::env::temp_dir().join(format!("gitt-format-test-{}", std::process::id()));
+        let repository = Repository::init(&directory).unwrap();
+        let tree = repository
+            .find_tree(repository.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let author =
+            git2::Signature::new("Ann Dev", "ann@example.com", &git2::Time::new(0, 60)).unwrap();
+        let committer =
+            git2::Signature::new("Cy Ops", "cy@example.com", &git2::Time::new(0, 0)).unwrap();
+        let parent = repository
+            .commit(None, &author, &committer, "Parent", &tree, &[])
+            .unwrap();
+        let parent = repository.find_commit(parent).unwrap();
+        let id = repository
+            .commit(
+                None,
+                &author,
+                &committer,
+                "Subject line\n\nBody text\n",
+                &tree,
+                &[&parent],
+            )
+            .unwrap();
+        let commit = repository.find_commit(id).unwrap();
+        let short = |commit: &Commit| crate::model::short_id(commit);
+
+        let refs = ["HEAD -> main".to_string(), "tag: v1".to_string()];
+        assert_eq!(
+            format_commit("%h%d %s", &commit, &refs),
+            format!(
+                "{} (HEAD -> main, tag: v1) {}",
+                short(&commit),
+                "Subject line"
+            )
+        );
+        assert_eq!(
+            format_commit("%H %P", &commit, &[]),
+            format!("{} {}", id, parent.id())
+        );
+        assert_eq!(
+            format_commit("%p%d|%D", &commit, &[]),
+            format!("{}|", short(&parent))
+        );
+        assert_eq!(
+            format_commit("%an <%ae> %ad%n%cn <%ce>", &commit, &[]),
+            "Ann Dev <ann@example.com> 1970-01-01T01:00:00+01:00\nCy Ops <cy@example.com>"
+        );
+        assert_eq!(format_commit("%b", &commit, &[]), "Body text\n");
+        assert_eq!(format_commit("100%% %x %a", &commit, &[]), "100% %x %a");
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+// Matches a path against a shell style pattern where `*` matches anything but a `/` and `?`
+// matches a single character. Patterns without a `/` are matched against the file name only, so
+// `Cargo.lock` matches the lockfile in any directory.
+pub fn matches(pattern: &str, path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    let candidate = if pattern.contains('/') {
+        path.as_ref()
+    } else {
+        path.rsplit('/').next().unwrap_or_default()
+    };
+    matches_from(
+        &pattern.chars().collect::<Vec<_>>(),
+        &candidate.chars().collect::<Vec<_>>(),
+    )
+}
+
+fn matches_from(pattern: &[char], candidate: &[char]) -> bool {
+    match pattern.split_first() {
+        None => candidate.is_empty(),
+        Some(('*', rest)) => {
+            // Try every possible length for the star, stopping at directory separators
+            (0..=candidate.len())
+                .take_while(|length| !candidate[..*length].contains(&'/'))
+                .any(|length| matches_from(rest, &candidate[length..]))
+        }
+        Some(('?', rest)) => match candidate.split_first() {
+            Some((c, candidate_rest)) if *c != '/' => matches_from(rest, candidate_rest),
+            _ => false,
+        },
+        Some((p, rest)) => match candidate.split_first() {
+            Some((c, candidate_rest)) if c == p => matches_from(rest, candidate_rest),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_without_a_slash_match_the_file_name() {
+        assert!(matches("Cargo.lock", Path::new("Cargo.lock")));
+        assert!(matches("Cargo.lock", Path::new("crates/core/Cargo.lock")));
+        assert!(matches("*.min.js", Path::new("static/app.min.js")));
+        assert!(!matches("*.min.js", Path::new("static/app.js")));
+    }
+
+    #[test]
+    fn patterns_with_a_slash_match_the_whole_path() {
+        assert!(matches("vendor/*.c", Path::new("vendor/zlib.c")));
+        assert!(!matches("vendor/*.c", Path::new("src/vendor/zlib.c")));
+        assert!(!matches("vendor/*.c", Path::new("vendor/zlib/inflate.c")));
+    }
+
+    #[test]
+    fn question_marks_match_one_character_but_not_a_slash() {
+        assert!(matches("?.txt", Path::new("a.txt")));
+        assert!(!matches("?.txt", Path::new("ab.txt")));
+        assert!(!matches("a?b", Path::new("a/b")));
+    }
+}
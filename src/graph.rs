@@ -0,0 +1,261 @@
+use std::collections::{HashMap, HashSet};
+
+use git2::{Commit, Oid};
+
+// Draws the history as one row per commit. Each lane waits for the commit it expects next: a
+// commit takes the lane expecting it and hands the lane on to its first parent, other lanes
+// expecting the same commit join it, and each further parent starts a lane of its own.
+//
+//   *-\   merge
+//   | *   topic
+//   * |   main
+//   *-/   base
+pub struct Graph {
+    lanes: Vec<Option<Oid>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    Commit,
+    Lane,
+    // A lane joining the commit's lane from the left or the right
+    JoinFromLeft,
+    JoinFromRight,
+    // A lane for another parent, started to the left or the right of the commit's lane
+    ForkLeft,
+    ForkRight,
+    // Between the commit and a lane that joins or forks
+    Across,
+}
+
+impl Cell {
+    pub fn as_char(self) -> char {
+        match self {
+            Cell::Empty => ' ',
+            Cell::Commit => '*',
+            Cell::Lane => '|',
+            Cell::JoinFromLeft | Cell::ForkRight => '\\',
+            Cell::JoinFromRight | Cell::ForkLeft => '/',
+            Cell::Across => '-',
+        }
+    }
+}
+
+// A row of the graph. Lane i is drawn in cells[2 * i] and the cells in between are only used to
+// connect lanes.
+pub struct GraphRow {
+    pub cells: Vec<Cell>,
+}
+
+impl std::fmt::Display for GraphRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for cell in &self.cells {
+            write!(f, "{}", cell.as_char())?;
+        }
+        Ok(())
+    }
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self { lanes: Vec::new() }
+    }
+
+    pub fn row(&mut self, id: Oid, parents: &[Oid]) -> GraphRow {
+        let column = match self.lanes.iter().position(|lane| *lane == Some(id)) {
+            Some(column) => column,
+            None => self.free_lane(),
+        };
+        let mut lanes: Vec<Cell> = self
+            .lanes
+            .iter()
+            .map(|lane| match lane {
+                Some(_) => Cell::Lane,
+                None => Cell::Empty,
+            })
+            .collect();
+        lanes.resize(self.lanes.len().max(column + 1), Cell::Empty);
+        lanes[column] = Cell::Commit;
+
+        let mut connected = Vec::new();
+        for (index, lane) in self.lanes.iter_mut().enumerate() {
+            if index != column && *lane == Some(id) {
+                *lane = None;
+                lanes[index] = if index < column {
+                    Cell::JoinFromLeft
+                } else {
+                    Cell::JoinFromRight
+                };
+                connected.push(index);
+            }
+        }
+        if column == self.lanes.len() {
+            self.lanes.push(None);
+        }
+        self.lanes[column] = parents.first().copied();
+        for parent in parents.iter().skip(1) {
+            let index = self.free_lane();
+            if index == self.lanes.len() {
+                self.lanes.push(None);
+            }
+            self.lanes[index] = Some(*parent);
+            if index >= lanes.len() {
+                lanes.resize(index + 1, Cell::Empty);
+            }
+            lanes[index] = if index < column {
+                Cell::ForkLeft
+            } else {
+                Cell::ForkRight
+            };
+            connected.push(index);
+        }
+
+        let mut cells = Vec::with_capacity(lanes.len() * 2);
+        for lane in &lanes {
+            cells.push(*lane);
+            cells.push(Cell::Empty);
+        }
+        for index in connected {
+            let (start, end) = (column.min(index), column.max(index));
+            for cell in &mut cells[2 * start + 1..2 * end] {
+                if *cell == Cell::Empty {
+                    *cell = Cell::Across;
+                }
+            }
+        }
+
+        while self.lanes.last() == Some(&None) {
+            self.lanes.pop();
+        }
+        GraphRow { cells }
+    }
+
+    // The lanes waiting for later commits, padded to `width` cells
+    pub fn passing(&self, width: usize) -> GraphRow {
+        let mut cells = vec![Cell::Empty; width];
+        for (index, lane) in self.lanes.iter().enumerate() {
+            if lane.is_some() && 2 * index < width {
+                cells[2 * index] = Cell::Lane;
+            }
+        }
+        GraphRow { cells }
+    }
+
+    fn free_lane(&self) -> usize {
+        self.lanes
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.lanes.len())
+    }
+}
+
+// The parents of each commit as far as the graph is concerned. When commits are filtered out the
+// parents are rewritten to the first listed commit along their first parents, so lanes lead to
+// commits that are actually shown. Parents listed above their children, which clock skew can
+// cause, are left out as no lane could wait for them.
+pub fn listed_parents(commits: &[Commit]) -> Vec<Vec<Oid>> {
+    let positions: HashMap<Oid, usize> = commits
+        .iter()
+        .enumerate()
+        .map(|(position, commit)| (commit.id(), position))
+        .collect();
+    let listed: HashSet<Oid> = positions.keys().copied().collect();
+    let mut rewritten: HashMap<Oid, Option<Oid>> = HashMap::new();
+    commits
+        .iter()
+        .enumerate()
+        .map(|(position, commit)| {
+            let mut parents = Vec::new();
+            for parent in commit.parents() {
+                if let Some(parent) = rewrite(parent, &listed, &mut rewritten) {
+                    if positions[&parent] > position && !parents.contains(&parent) {
+                        parents.push(parent);
+                    }
+                }
+            }
+            parents
+        })
+        .collect()
+}
+
+fn rewrite(
+    commit: Commit,
+    listed: &HashSet<Oid>,
+    rewritten: &mut HashMap<Oid, Option<Oid>>,
+) -> Option<Oid> {
+    let mut skipped = Vec::new();
+    let mut current = Some(commit);
+    let found = loop {
+        let commit = match current {
+            Some(commit) => commit,
+            None => break None,
+        };
+        if listed.contains(&commit.id()) {
+            break Some(commit.id());
+        }
+        if let Some(found) = rewritten.get(&commit.id()) {
+            break *found;
+        }
+        skipped.push(commit.id());
+        current = commit.parent(0).ok();
+    };
+    for id in skipped {
+        rewritten.insert(id, found);
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u8) -> Oid {
+        Oid::from_bytes(&[n; 20]).unwrap()
+    }
+
+    fn draw(commits: &[(Oid, Vec<Oid>)]) -> Vec<String> {
+        let mut graph = Graph::new();
+        commits
+            .iter()
+            .map(|(commit, parents)| {
+                graph
+                    .row(*commit, parents)
+                    .to_string()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn draws_a_merge() {
+        let (merge, topic, main, base) = (id(1), id(2), id(3), id(4));
+        let rows = draw(&[
+            (merge, vec![main, topic]),
+            (topic, vec![base]),
+            (main, vec![base]),
+            (base, vec![]),
+        ]);
+        assert_eq!(rows, vec!["*-\\", "| *", "* |", "*-/"]);
+    }
+
+    #[test]
+    fn reuses_lanes_that_ended() {
+        let rows = draw(&[
+            (id(1), vec![id(3)]),
+            (id(2), vec![id(4)]),
+            (id(3), vec![]),
+            (id(4), vec![]),
+            (id(5), vec![]),
+        ]);
+        assert_eq!(rows, vec!["*", "| *", "* |", "  *", "*"]);
+    }
+
+    #[test]
+    fn passing_rows_show_the_waiting_lanes() {
+        let mut graph = Graph::new();
+        graph.row(id(1), &[id(2), id(3)]);
+        assert_eq!(graph.passing(4).to_string(), "| | ");
+    }
+}
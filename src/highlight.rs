@@ -0,0 +1,173 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use tui::text::Spans;
+
+use crate::ansi;
+use crate::external;
+
+// How long the diff highlighter gets for each diff before gitt styles the patch itself
+const HIGHLIGHT_TIMEOUT: Duration = Duration::from_secs(2);
+// How many patches' output is kept, so moving back to a commit doesn't run the highlighter again
+const CACHED_PATCHES: usize = 64;
+
+// A file's patch as a diff highlighter printed it
+#[derive(Clone)]
+pub enum HighlightedFile {
+    // One line for each line of the patch, so the file keeps its hunks and collapsing
+    Lines(Vec<Spans<'static>>),
+    // Output that doesn't line up with the patch, like a side by side view, shown as it is
+    Block(Vec<Spans<'static>>),
+}
+
+// What the highlighter is given: the lines of each shown file's patch colored like git colors
+// them, and the width it should fit its output to
+#[derive(Hash)]
+pub struct Patch {
+    pub command: String,
+    pub columns: usize,
+    pub files: Vec<Vec<String>>,
+}
+
+impl Patch {
+    fn key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+// None when the highlighter failed, gitt styles the patch itself then
+type Output = Option<Vec<HighlightedFile>>;
+
+// The highlighter's output for the patches it was run on. The interface runs it on a thread of
+// its own so moving through the commits isn't held up while it works.
+#[derive(Default)]
+pub struct Highlights {
+    done: HashMap<u64, Output>,
+    worker: Option<Worker>,
+    // The patch the interface is waiting for
+    wanted: Option<u64>,
+}
+
+// A single thread running the highlighter on one patch at a time. Patches that were asked for
+// while it was busy are skipped for the latest one, so moving quickly through the commits doesn't
+// pile up highlighter processes.
+struct Worker {
+    requests: Sender<(u64, Patch)>,
+    results: Receiver<(u64, Output)>,
+}
+
+impl Worker {
+    fn new() -> Self {
+        let (requests, pending) = channel::<(u64, Patch)>();
+        let (finished, results) = channel();
+        std::thread::spawn(move || {
+            while let Ok(request) = pending.recv() {
+                let (key, patch) = pending.try_iter().last().unwrap_or(request);
+                if finished.send((key, highlight(&patch))).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { requests, results }
+    }
+}
+
+impl Highlights {
+    // Returns the output if the patch was highlighted before, otherwise asks the worker for it and
+    // returns None. Only the latest patch is waited for.
+    pub fn get_or_start(&mut self, patch: Patch) -> Output {
+        let key = patch.key();
+        if let Some(output) = self.done.get(&key) {
+            return output.clone();
+        }
+        if self.wanted != Some(key) {
+            let worker = self.worker.get_or_insert_with(Worker::new);
+            if worker.requests.send((key, patch)).is_err() {
+                self.worker = None;
+                return None;
+            }
+            self.wanted = Some(key);
+        }
+        None
+    }
+
+    // Returns the output for the patch, running the highlighter and waiting for it if needed
+    pub fn get_or_run(&mut self, patch: Patch) -> Output {
+        let key = patch.key();
+        if let Some(output) = self.done.get(&key) {
+            return output.clone();
+        }
+        let output = highlight(&patch);
+        self.insert(key, output.clone());
+        output
+    }
+
+    // Keeps the output of the patches the worker finished, returning true when the one waited for
+    // is among them
+    pub fn poll(&mut self) -> bool {
+        let results: Vec<_> = match &self.worker {
+            Some(worker) => worker.results.try_iter().collect(),
+            None => return false,
+        };
+        let mut wanted = false;
+        for (key, output) in results {
+            if self.wanted == Some(key) {
+                self.wanted = None;
+                wanted = true;
+            }
+            self.insert(key, output);
+        }
+        wanted
+    }
+
+    fn insert(&mut self, key: u64, output: Output) {
+        if self.done.len() >= CACHED_PATCHES {
+            self.done.clear();
+        }
+        self.done.insert(key, output);
+    }
+}
+
+// Pipes the patch through the highlighter, returning the output for each file in order. The whole
+// patch goes through at once, and when the output doesn't have a line for each line of the patch
+// every file goes through on its own instead.
+fn highlight(patch: &Patch) -> Output {
+    let deadline = Instant::now() + HIGHLIGHT_TIMEOUT;
+    let run = |lines: &[String]| {
+        let timeout = deadline.checked_duration_since(Instant::now())?;
+        let input = lines.iter().map(|line| format!("{}\n", line)).collect();
+        external::filter(&patch.command, input, patch.columns, timeout)
+            .ok()
+            .map(|output| ansi::parse(&output))
+    };
+
+    let files = &patch.files;
+    let mut output = run(&files.concat())?.into_iter();
+    if output.len() == files.iter().map(Vec::len).sum::<usize>() {
+        return Some(
+            files
+                .iter()
+                .map(|file| HighlightedFile::Lines(output.by_ref().take(file.len()).collect()))
+                .collect(),
+        );
+    }
+    files
+        .iter()
+        .map(|file| {
+            if file.is_empty() {
+                return Some(HighlightedFile::Lines(Vec::new()));
+            }
+            let output = run(file)?;
+            Some(if output.len() == file.len() {
+                HighlightedFile::Lines(output)
+            } else {
+                HighlightedFile::Block(output)
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use git2::{Commit, Oid, Repository};
+use tui::style::{Color, Modifier, Style};
+use tui::text::Spans;
+
+use crate::format::{self, Decorations};
+use crate::graph::{self, Cell, Graph};
+use crate::model::short_id;
+
+// Width the diffstat bars are drawn to, about what a wide terminal gives the details pane
+pub const DETAILS_WIDTH: usize = 100;
+
+const LANE_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+];
+
+const STYLESHEET: &str = "
+body { background: #1e1e1e; color: #d4d4d4; font-family: monospace; margin: 1em; }
+a { color: inherit; }
+table { border-collapse: collapse; }
+td { padding: 0 1em 0 0; white-space: pre; vertical-align: top; }
+td.graph { padding: 0; }
+tr.detailed { color: #11a8cd; font-weight: bold; }
+.ref { border: 1px solid; border-radius: 3px; padding: 0 2px; margin-right: 4px; }
+section { border-top: 1px solid #767676; margin-top: 1em; }
+pre { margin: 0.5em 0; }
+";
+
+// Writes a single HTML page with the commit list, its graph and the refs pointing at each
+// commit, followed by the `details` of some of the commits as the details pane styled them
+pub fn write_page<'a>(
+    output: &mut impl Write,
+    repository: &Repository,
+    commits: impl Iterator<Item = Commit<'a>>,
+    date_format: Option<&str>,
+    details: &HashMap<Oid, Vec<Spans<'static>>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let title = repository
+        .workdir()
+        .unwrap_or_else(|| repository.path())
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let decorations = Decorations::new(repository)?;
+    let commits: Vec<_> = commits.collect();
+
+    writeln!(output, "<!DOCTYPE html>")?;
+    writeln!(output, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(output, "<title>gitt - {}</title>", escape(&title))?;
+    writeln!(output, "<style>{}</style>\n</head>\n<body>", STYLESHEET)?;
+
+    writeln!(output, "<table>")?;
+    let mut graph = Graph::new();
+    for (commit, parents) in commits.iter().zip(graph::listed_parents(&commits)) {
+        let row = graph.row(commit.id(), &parents);
+        let graph_html: String = row
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(index, cell)| match cell {
+                Cell::Empty => " ".to_string(),
+                cell => styled(
+                    &cell.as_char().to_string(),
+                    Style::default().fg(LANE_COLORS[(index / 2) % LANE_COLORS.len()]),
+                ),
+            })
+            .collect();
+        let id = if details.contains_key(&commit.id()) {
+            format!("<a href=\"#{}\">{}</a>", commit.id(), short_id(commit))
+        } else {
+            short_id(commit)
+        };
+        let refs: String = decorations
+            .get(commit.id())
+            .iter()
+            .map(|name| {
+                format!(
+                    "<span class=\"ref\" style=\"color: {}\">{}</span>",
+                    css_color(ref_color(name)).unwrap_or_default(),
+                    escape(name)
+                )
+            })
+            .collect();
+        writeln!(
+            output,
+            "<tr{}><td class=\"graph\">{}</td><td>{}</td><td>{}{}</td><td>{}</td><td>{}</td></tr>",
+            if details.contains_key(&commit.id()) {
+                " class=\"detailed\""
+            } else {
+                ""
+            },
+            graph_html,
+            id,
+            refs,
+            escape(commit.summary().unwrap_or_default()),
+            escape(&commit.author().to_string()),
+            format::format_time(&commit.time(), date_format),
+        )?;
+    }
+    writeln!(output, "</table>")?;
+
+    for (commit, lines) in commits
+        .iter()
+        .filter_map(|commit| Some((commit, details.get(&commit.id())?)))
+    {
+        writeln!(output, "<section id=\"{}\">\n<pre>", commit.id())?;
+        for line in lines {
+            writeln!(output, "{}", spans_html(line))?;
+        }
+        writeln!(output, "</pre>\n</section>")?;
+    }
+    writeln!(output, "</body>\n</html>")?;
+    Ok(())
+}
+
+// Colored like git log --decorate colors them
+fn ref_color(name: &str) -> Color {
+    if name.starts_with("HEAD") {
+        Color::Cyan
+    } else if name.starts_with("tag: ") {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
+fn spans_html(spans: &Spans) -> String {
+    spans
+        .0
+        .iter()
+        .map(|span| styled(&span.content, span.style))
+        .collect()
+}
+
+fn styled(text: &str, style: Style) -> String {
+    let (fg, bg) = if style.add_modifier.contains(Modifier::REVERSED) {
+        (
+            style.bg.or(Some(Color::Black)),
+            style.fg.or(Some(Color::Gray)),
+        )
+    } else {
+        (style.fg, style.bg)
+    };
+    let mut css = Vec::new();
+    if let Some(color) = fg.and_then(css_color) {
+        css.push(format!("color: {}", color));
+    }
+    if let Some(color) = bg.and_then(css_color) {
+        css.push(format!("background: {}", color));
+    }
+    if style.add_modifier.contains(Modifier::BOLD) {
+        css.push("font-weight: bold".to_string());
+    }
+    if style.add_modifier.contains(Modifier::ITALIC) {
+        css.push("font-style: italic".to_string());
+    }
+    if style.add_modifier.contains(Modifier::UNDERLINED) {
+        css.push("text-decoration: underline".to_string());
+    }
+    if style.add_modifier.contains(Modifier::DIM) {
+        css.push("opacity: 0.6".to_string());
+    }
+    if css.is_empty() {
+        escape(text)
+    } else {
+        format!("<span style=\"{}\">{}</span>", css.join("; "), escape(text))
+    }
+}
+
+// The colors of a common dark terminal theme, so the page looks like gitt does
+fn css_color(color: Color) -> Option<String> {
+    let hex = match color {
+        Color::Reset => return None,
+        Color::Black => "#000000",
+        Color::Red => "#cd3131",
+        Color::Green => "#0dbc79",
+        Color::Yellow => "#e5e510",
+        Color::Blue => "#2472c8",
+        Color::Magenta => "#bc3fbc",
+        Color::Cyan => "#11a8cd",
+        Color::Gray => "#e5e5e5",
+        Color::DarkGray => "#767676",
+        Color::LightRed => "#f14c4c",
+        Color::LightGreen => "#23d18b",
+        Color::LightYellow => "#f5f543",
+        Color::LightBlue => "#3b8eea",
+        Color::LightMagenta => "#d670d6",
+        Color::LightCyan => "#29b8db",
+        Color::White => "#ffffff",
+        Color::Rgb(r, g, b) => return Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+        Color::Indexed(index) => return Some(indexed_color(index)),
+    };
+    Some(hex.to_string())
+}
+
+// The xterm 256 color palette beyond the 16 named colors
+fn indexed_color(index: u8) -> String {
+    const NAMED: [Color; 16] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::Gray,
+        Color::DarkGray,
+        Color::LightRed,
+        Color::LightGreen,
+        Color::LightYellow,
+        Color::LightBlue,
+        Color::LightMagenta,
+        Color::LightCyan,
+        Color::White,
+    ];
+    match index {
+        0..=15 => css_color(NAMED[index as usize]).unwrap_or_default(),
+        16..=231 => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                level(index / 36),
+                level((index / 6) % 6),
+                level(index % 6)
+            )
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            format!("#{:02x}{:02x}{:02x}", level, level, level)
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,160 @@
+use std::io::Write;
+use std::path::Path;
+
+use git2::{Commit, Repository};
+use serde::Serialize;
+
+use crate::diff_settings::DiffSettings;
+use crate::format::{self, Decorations};
+use crate::model::{self, short_id, CommitFilter, PatchFile};
+
+// How much of each commit's diff goes into the JSON
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonDetail {
+    Commits,
+    Stat,
+    Patch,
+}
+
+impl std::str::FromStr for JsonDetail {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "commits" => Ok(JsonDetail::Commits),
+            "stat" => Ok(JsonDetail::Stat),
+            "patch" => Ok(JsonDetail::Patch),
+            _ => Err(format!("Unknown JSON detail {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CommitRecord {
+    id: String,
+    short_id: String,
+    parents: Vec<String>,
+    author: SignatureRecord,
+    committer: SignatureRecord,
+    message: String,
+    refs: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<Vec<FileRecord>>,
+}
+
+#[derive(Serialize)]
+struct SignatureRecord {
+    name: String,
+    email: String,
+    // RFC 3339 with the signature's own offset, the same as the commit list shows
+    date: String,
+    timestamp: i64,
+    offset_minutes: i32,
+}
+
+#[derive(Serialize)]
+struct FileRecord {
+    status: char,
+    old_path: Option<String>,
+    new_path: Option<String>,
+    additions: usize,
+    deletions: usize,
+    binary: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hunks: Option<Vec<HunkRecord>>,
+}
+
+#[derive(Serialize)]
+struct HunkRecord {
+    header: String,
+    old_start: u32,
+    old_lines: u32,
+    new_start: u32,
+    new_lines: u32,
+    lines: Vec<String>,
+}
+
+impl SignatureRecord {
+    fn new(signature: &git2::Signature) -> Self {
+        let when = signature.when();
+        Self {
+            name: signature.name().unwrap_or_default().to_string(),
+            email: signature.email().unwrap_or_default().to_string(),
+            date: format::format_time(&when, None),
+            timestamp: when.seconds(),
+            offset_minutes: when.offset_minutes(),
+        }
+    }
+}
+
+impl FileRecord {
+    fn new(file: PatchFile, detail: JsonDetail) -> Self {
+        let path = |path: Option<&Path>| path.map(|path| path.to_string_lossy().to_string());
+        let hunks = match detail {
+            JsonDetail::Patch => Some(
+                file.hunks
+                    .into_iter()
+                    .map(|hunk| HunkRecord {
+                        header: hunk.header,
+                        old_start: hunk.old_start,
+                        old_lines: hunk.old_lines,
+                        new_start: hunk.new_start,
+                        new_lines: hunk.new_lines,
+                        // Lines keep their origin in front like they have in a patch
+                        lines: hunk
+                            .lines
+                            .into_iter()
+                            .map(|(origin, content)| format!("{}{}", origin, content))
+                            .collect(),
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        Self {
+            status: file.status,
+            old_path: path(file.old_path.as_deref()),
+            new_path: path(file.new_path.as_deref()),
+            additions: file.stat.additions,
+            deletions: file.stat.deletions,
+            binary: file.stat.binary,
+            hunks,
+        }
+    }
+}
+
+// Writes each commit as a line of JSON, diffing them with the settings and filters the details
+// pane would use
+pub fn write_commits<'a>(
+    output: &mut impl Write,
+    repository: &Repository,
+    settings: &DiffSettings,
+    filters: &[CommitFilter],
+    commits: impl Iterator<Item = Commit<'a>>,
+    detail: JsonDetail,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let decorations = Decorations::new(repository)?;
+    for commit in commits {
+        let files = match detail {
+            JsonDetail::Commits => None,
+            _ => Some(
+                model::patch_files(repository, settings, filters, &commit)
+                    .into_iter()
+                    .map(|file| FileRecord::new(file, detail))
+                    .collect(),
+            ),
+        };
+        let record = CommitRecord {
+            id: commit.id().to_string(),
+            short_id: short_id(&commit),
+            parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+            author: SignatureRecord::new(&commit.author()),
+            committer: SignatureRecord::new(&commit.committer()),
+            message: commit.message().unwrap_or_default().to_string(),
+            refs: decorations.get(commit.id()).to_vec(),
+            files,
+        };
+        writeln!(output, "{}", serde_json::to_string(&record)?)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,300 @@
+use std::collections::{HashMap, HashSet};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::de::{Deserializer, Error};
+use serde::Deserialize;
+
+// The actions keys can be bound to in the [keys] section of the config file, with their default
+// keys. The controller matches on the default keys, a rebound key is translated to the first of
+// them that no other action uses, since the controller picks the action by the pane it's in.
+const ACTIONS: &[(&str, &[&str])] = &[
+    ("quit", &["q"]),
+    ("next-pane", &["tab"]),
+    ("down", &["j", "down"]),
+    ("up", &["k", "up"]),
+    ("first", &["g"]),
+    ("last", &["G"]),
+    ("page-down", &["pagedown", "ctrl-f"]),
+    ("page-up", &["pageup", "ctrl-b"]),
+    ("half-page-down", &["ctrl-d"]),
+    ("half-page-up", &["ctrl-u"]),
+    ("select", &["enter"]),
+    ("back", &["esc"]),
+    ("open", &["l", "right"]),
+    ("close", &["h", "left"]),
+    ("mark", &["m"]),
+    ("swap", &["x"]),
+    ("pick", &["space"]),
+    ("tree", &["t"]),
+    ("export-patches", &["E"]),
+    ("difftool", &["D"]),
+    ("edit", &["e"]),
+    ("line-range", &["L"]),
+    ("file-view", &["v"]),
+    ("blame", &["b"]),
+    ("parent", &["p"]),
+    ("collapse", &["z", "space"]),
+    ("next", &["]"]),
+    ("previous", &["["]),
+    ("yank", &["y"]),
+    ("word-diff", &["w"]),
+    ("syntax-highlighting", &["S"]),
+    ("highlighter", &["H"]),
+    ("whitespace", &["W"]),
+    ("diff-algorithm", &["a"]),
+    ("indent-heuristic", &["i"]),
+    ("renames", &["R"]),
+    ("copies", &["C"]),
+    ("more-context", &["+", "="]),
+    ("less-context", &["-"]),
+];
+
+type Key = (KeyCode, KeyModifiers);
+
+// Keys the user bound to actions in place of their default keys
+#[derive(Clone, Debug, Default)]
+pub struct Keymap {
+    // Each bound key and the default key of its action
+    bound: HashMap<Key, Key>,
+    // Default keys of rebound actions that no other action uses
+    unbound: HashSet<Key>,
+}
+
+impl Keymap {
+    pub fn new(bindings: &HashMap<String, Vec<String>>) -> Result<Self, String> {
+        let mut keymap = Self::default();
+        let mut bound_by: HashMap<Key, &str> = HashMap::new();
+        let mut replaced = HashSet::new();
+        for (action, keys) in bindings {
+            let defaults = ACTIONS
+                .iter()
+                .find(|(name, _)| name == action)
+                .map(|(_, defaults)| *defaults)
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown action `{}` in [keys], expected one of {}",
+                        action,
+                        ACTIONS
+                            .iter()
+                            .map(|(name, _)| *name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })?;
+            // pick and collapse share space, so a key translated to it would collapse a file in
+            // the diff pane
+            let target = defaults
+                .iter()
+                .find(|key| {
+                    ACTIONS
+                        .iter()
+                        .all(|(name, keys)| name == action || !keys.contains(key))
+                })
+                .ok_or_else(|| {
+                    let other = ACTIONS
+                        .iter()
+                        .find(|(name, keys)| name != action && keys.contains(&defaults[0]))
+                        .map_or("another action", |(name, _)| name);
+                    format!(
+                        "{} can't be rebound, its key {} is also used by {}",
+                        action, defaults[0], other
+                    )
+                })
+                .and_then(|key| parse_key(key))?;
+            for key in keys {
+                let key = parse_key(key).map_err(|e| format!("{} for {}", e, action))?;
+                if let Some(other) = bound_by.insert(key, action) {
+                    if other != action {
+                        return Err(format!(
+                            "{} is bound to both {} and {}",
+                            describe(key),
+                            other,
+                            action
+                        ));
+                    }
+                }
+                keymap.bound.insert(key, target);
+            }
+            replaced.insert(action.as_str());
+        }
+        let kept: HashSet<Key> = ACTIONS
+            .iter()
+            .filter(|(name, _)| !replaced.contains(name))
+            .flat_map(|(_, defaults)| defaults.iter().map(|key| parse_key(key).unwrap()))
+            .collect();
+        keymap.unbound = ACTIONS
+            .iter()
+            .filter(|(name, _)| replaced.contains(name))
+            .flat_map(|(_, defaults)| defaults.iter().map(|key| parse_key(key).unwrap()))
+            .filter(|key| !kept.contains(key))
+            .collect();
+        Ok(keymap)
+    }
+
+    // The key the controller should act on, None for a default key the user moved elsewhere
+    pub fn translate(&self, event: KeyEvent) -> Option<KeyEvent> {
+        let key = normalize(event);
+        if let Some((code, modifiers)) = self.bound.get(&key) {
+            return Some(KeyEvent::new(*code, *modifiers));
+        }
+        if self.unbound.contains(&key) {
+            return None;
+        }
+        Some(event)
+    }
+}
+
+// Actions are bound to a key or a list of keys
+impl<'de> Deserialize<'de> for Keymap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Keys {
+            One(String),
+            Many(Vec<String>),
+        }
+        let bindings: HashMap<String, Keys> = HashMap::deserialize(deserializer)?;
+        let bindings = bindings
+            .into_iter()
+            .map(|(action, keys)| match keys {
+                Keys::One(key) => (action, vec![key]),
+                Keys::Many(keys) => (action, keys),
+            })
+            .collect();
+        Self::new(&bindings).map_err(D::Error::custom)
+    }
+}
+
+// Shift is part of the character for letters and symbols, so only control and alt are compared
+fn normalize(event: KeyEvent) -> Key {
+    (
+        event.code,
+        event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT),
+    )
+}
+
+// Keys are a character, a name like enter or pagedown, or either with ctrl- or alt- in front
+fn parse_key(text: &str) -> Result<Key, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = text;
+    loop {
+        let lower = rest.to_lowercase();
+        if rest.chars().count() > 1 && lower.starts_with("ctrl-") {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = &rest[5..];
+        } else if rest.chars().count() > 1 && lower.starts_with("alt-") {
+            modifiers |= KeyModifiers::ALT;
+            rest = &rest[4..];
+        } else {
+            break;
+        }
+    }
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest.to_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(number @ 1..=12) => KeyCode::F(number),
+                _ => return Err(format!("Unknown key `{}`", text)),
+            },
+        },
+    };
+    Ok((code, modifiers))
+}
+
+fn describe((code, modifiers): Key) -> String {
+    let name = match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(number) => format!("f{}", number),
+        code => format!("{:?}", code).to_lowercase(),
+    };
+    let mut prefix = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        prefix.push_str("ctrl-");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        prefix.push_str("alt-");
+    }
+    prefix + &name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_characters_and_named_keys() {
+        assert_eq!(parse_key("q"), Ok((KeyCode::Char('q'), KeyModifiers::NONE)));
+        assert_eq!(parse_key("Q"), Ok((KeyCode::Char('Q'), KeyModifiers::NONE)));
+        assert_eq!(
+            parse_key("space"),
+            Ok((KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("PageDown"),
+            Ok((KeyCode::PageDown, KeyModifiers::NONE))
+        );
+        assert_eq!(parse_key("f5"), Ok((KeyCode::F(5), KeyModifiers::NONE)));
+        assert!(parse_key("f13").is_err());
+        assert!(parse_key("nope").is_err());
+    }
+
+    #[test]
+    fn parses_modifiers() {
+        assert_eq!(
+            parse_key("ctrl-c"),
+            Ok((KeyCode::Char('c'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("Ctrl-Alt-enter"),
+            Ok((KeyCode::Enter, KeyModifiers::CONTROL | KeyModifiers::ALT))
+        );
+        // A lone dash is the key itself
+        assert_eq!(parse_key("-"), Ok((KeyCode::Char('-'), KeyModifiers::NONE)));
+        assert_eq!(
+            parse_key("alt--"),
+            Ok((KeyCode::Char('-'), KeyModifiers::ALT))
+        );
+    }
+
+    fn bindings(action: &str, key: &str) -> HashMap<String, Vec<String>> {
+        let mut bindings = HashMap::new();
+        bindings.insert(action.to_string(), vec![key.to_string()]);
+        bindings
+    }
+
+    #[test]
+    fn translates_to_a_default_key_no_other_action_uses() {
+        let keymap = Keymap::new(&bindings("collapse", "c")).unwrap();
+        let event = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE);
+        assert_eq!(
+            keymap.translate(event),
+            Some(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::NONE))
+        );
+        assert!(Keymap::new(&bindings("pick", "P")).is_err());
+    }
+
+    #[test]
+    fn describes_keys_as_they_are_written() {
+        for key in ["q", "space", "ctrl-c", "alt-enter", "f5"] {
+            assert_eq!(describe(parse_key(key).unwrap()), key);
+        }
+    }
+}
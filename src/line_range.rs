@@ -0,0 +1,504 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use git2::{DiffFindOptions, DiffOptions, Oid, Repository, Sort, Tree};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lines {
+    Numbers { start: usize, end: usize },
+    // The lines of the function with this name, found again in every commit so the function can
+    // be followed when it moves around the file
+    Function(String),
+}
+
+// A range of lines in a file as given to -L, like git log -L start,end:file or -L :name:file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineRangeSpec {
+    pub path: PathBuf,
+    pub lines: Lines,
+}
+
+impl FromStr for LineRangeSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid line range, expected start,end:file or :funcname:file: {}",
+                s
+            )
+        };
+        if let Some(function) = s.strip_prefix(':') {
+            let (name, path) = function.split_once(':').ok_or_else(invalid)?;
+            if name.is_empty() || path.is_empty() {
+                return Err(invalid());
+            }
+            return Ok(Self {
+                path: PathBuf::from(path),
+                lines: Lines::Function(name.to_string()),
+            });
+        }
+        let (range, path) = s.split_once(':').ok_or_else(invalid)?;
+        let (start, end) = range.split_once(',').ok_or_else(invalid)?;
+        let start: usize = start.parse().map_err(|_| invalid())?;
+        // The end can also be given as a number of lines, like git's start,+count
+        let end: usize = match end.strip_prefix('+') {
+            Some(count) => {
+                start
+                    + count
+                        .parse::<usize>()
+                        .map_err(|_| invalid())?
+                        .saturating_sub(1)
+            }
+            None => end.parse().map_err(|_| invalid())?,
+        };
+        if start == 0 || end < start || path.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            path: PathBuf::from(path),
+            lines: Lines::Numbers { start, end },
+        })
+    }
+}
+
+// Where the tracked lines are in one commit, the file can be renamed along the way
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeAt {
+    pub path: PathBuf,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl RangeAt {
+    // Whether a hunk covering the given new side lines overlaps the range
+    pub fn overlaps(&self, start: usize, lines: usize) -> bool {
+        let end = start + lines.max(1) - 1;
+        start <= self.end && end >= self.start
+    }
+}
+
+// The commits that changed a range of lines, found by walking back from a starting commit and
+// carrying the range through every hunk that moves it
+pub struct LineHistory {
+    pub spec: LineRangeSpec,
+    pub ranges: HashMap<Oid, RangeAt>,
+    pub commits: HashSet<Oid>,
+}
+
+impl LineHistory {
+    pub fn new(
+        repository: &Repository,
+        start: Oid,
+        spec: LineRangeSpec,
+    ) -> Result<Self, git2::Error> {
+        let function = match &spec.lines {
+            Lines::Function(name) => Some(name.as_str()),
+            Lines::Numbers { .. } => None,
+        };
+        let tree = repository.find_commit(start)?.tree()?;
+        let blob = blob_at(repository, &tree, &spec.path)
+            .ok_or_else(|| git2::Error::from_str(&format!("{} not found", spec.path.display())))?;
+        let content = String::from_utf8_lossy(blob.content());
+        let (first, last) = match &spec.lines {
+            Lines::Numbers { start, end } => {
+                // Like git log -L, a range running past the end of the file is refused
+                let count = content.lines().count();
+                if *end > count {
+                    return Err(git2::Error::from_str(&format!(
+                        "{} has only {} {}",
+                        spec.path.display(),
+                        count,
+                        if count == 1 { "line" } else { "lines" }
+                    )));
+                }
+                (*start, *end)
+            }
+            Lines::Function(name) => {
+                function_range(&content, name, &spec.path).ok_or_else(|| {
+                    git2::Error::from_str(&format!(
+                        "Function {} not found in {}",
+                        name,
+                        spec.path.display()
+                    ))
+                })?
+            }
+        };
+        let mut ranges: HashMap<Oid, RangeAt> = HashMap::new();
+        let mut commits = HashSet::new();
+        ranges.insert(
+            start,
+            RangeAt {
+                path: spec.path.clone(),
+                start: first,
+                end: last,
+            },
+        );
+
+        // Children are visited before their parents so a commit's range is known when it's
+        // reached
+        let mut walker = repository.revwalk()?;
+        walker.set_sorting(Sort::TOPOLOGICAL)?;
+        walker.push(start)?;
+        for oid in walker {
+            let oid = oid?;
+            let range = match ranges.get(&oid) {
+                Some(range) => range.clone(),
+                None => continue,
+            };
+            let commit = repository.find_commit(oid)?;
+            let tree = commit.tree()?;
+            let blob = match blob_at(repository, &tree, &range.path) {
+                Some(blob) => blob,
+                None => continue,
+            };
+
+            // A commit only changed the lines if they differ from every parent, so merges that
+            // took the lines unchanged from one side are left out
+            let mut changed = true;
+            for parent in commit.parents() {
+                let parent_tree = parent.tree()?;
+                let parent_path = match blob_at(repository, &parent_tree, &range.path) {
+                    Some(_) => Some(range.path.clone()),
+                    None => renamed_from(repository, &parent_tree, &tree, &range.path)?,
+                };
+                let parent_blob = parent_path
+                    .as_ref()
+                    .and_then(|path| blob_at(repository, &parent_tree, path));
+                let (parent_path, parent_blob) = match (parent_path, parent_blob) {
+                    (Some(path), Some(blob)) => (path, blob),
+                    // The file was added here, so this commit wrote the lines
+                    _ => continue,
+                };
+                let (touched, parent_range) = if parent_blob.id() == blob.id() {
+                    (false, Some((range.start, range.end)))
+                } else {
+                    // Functions are looked up by name, and only count as changed when their
+                    // text did, falling back to following the lines when the name is gone
+                    let found = function.and_then(|name| {
+                        let content = String::from_utf8_lossy(blob.content());
+                        let parent_content = String::from_utf8_lossy(parent_blob.content());
+                        let (start, end) = function_range(&parent_content, name, &parent_path)?;
+                        let changed = slice_lines(&content, range.start, range.end)
+                            != slice_lines(&parent_content, start, end);
+                        Some((changed, Some((start, end))))
+                    });
+                    match found {
+                        Some(found) => found,
+                        None => map_range(&hunks(&parent_blob, &blob)?, range.start, range.end),
+                    }
+                };
+                if !touched {
+                    changed = false;
+                }
+                if let Some((start, end)) = parent_range {
+                    // Ranges reaching a commit from several children are merged
+                    ranges
+                        .entry(parent.id())
+                        .and_modify(|existing| {
+                            if existing.path == parent_path {
+                                existing.start = existing.start.min(start);
+                                existing.end = existing.end.max(end);
+                            }
+                        })
+                        .or_insert(RangeAt {
+                            path: parent_path,
+                            start,
+                            end,
+                        });
+                }
+            }
+            if changed {
+                commits.insert(oid);
+            }
+        }
+        Ok(Self {
+            spec,
+            ranges,
+            commits,
+        })
+    }
+}
+
+fn blob_at<'r>(repository: &'r Repository, tree: &Tree, path: &Path) -> Option<git2::Blob<'r>> {
+    let entry = tree.get_path(path).ok()?;
+    repository.find_blob(entry.id()).ok()
+}
+
+fn renamed_from(
+    repository: &Repository,
+    old: &Tree,
+    new: &Tree,
+    path: &Path,
+) -> Result<Option<PathBuf>, git2::Error> {
+    let mut diff = repository.diff_tree_to_tree(Some(old), Some(new), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+    let old_path = diff
+        .deltas()
+        .find(|delta| delta.new_file().path() == Some(path))
+        .and_then(|delta| delta.old_file().path().map(|p| p.to_path_buf()));
+    Ok(old_path)
+}
+
+// A hunk as (old start, old line count, new start, new line count)
+type Hunk = (usize, usize, usize, usize);
+
+fn hunks(old: &git2::Blob, new: &git2::Blob) -> Result<Vec<Hunk>, git2::Error> {
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    let patch = git2::Patch::from_blobs(old, None, new, None, Some(&mut options))?;
+    (0..patch.num_hunks())
+        .map(|index| {
+            patch.hunk(index).map(|(hunk, _)| {
+                (
+                    hunk.old_start() as usize,
+                    hunk.old_lines() as usize,
+                    hunk.new_start() as usize,
+                    hunk.new_lines() as usize,
+                )
+            })
+        })
+        .collect()
+}
+
+// Works out whether the hunks touch the range, and where the range was before them. Lines that
+// were rewritten map to the whole of the old side of their hunk. Returns None for the old range
+// if every line of the range was added by the hunks.
+fn map_range(hunks: &[Hunk], start: usize, end: usize) -> (bool, Option<(usize, usize)>) {
+    // Hunks with no lines on one side sit after the line they are numbered with
+    let span = |start: usize, lines: usize| {
+        if lines == 0 {
+            (start + 1, start + 1)
+        } else {
+            (start, start + lines)
+        }
+    };
+    let touched = hunks.iter().any(|&(_, _, new_start, new_lines)| {
+        let (first, after) = span(new_start, new_lines);
+        if new_lines == 0 {
+            // Deleted lines only count when they were between lines of the range
+            start < first && first <= end
+        } else {
+            first <= end && after > start
+        }
+    });
+
+    let map = |line: usize, at_start: bool| {
+        let mut shift: isize = 0;
+        for &(old_start, old_lines, new_start, new_lines) in hunks {
+            let (old_first, old_after) = span(old_start, old_lines);
+            let (new_first, new_after) = span(new_start, new_lines);
+            if line >= new_after {
+                shift += (old_after - old_first) as isize - (new_after - new_first) as isize;
+            } else if line >= new_first {
+                return if at_start {
+                    old_first as isize
+                } else {
+                    old_after as isize - 1
+                };
+            } else {
+                break;
+            }
+        }
+        line as isize + shift
+    };
+    let old_start = map(start, true).max(1);
+    let old_end = map(end, false);
+    if old_end < old_start {
+        (touched, None)
+    } else {
+        (touched, Some((old_start as usize, old_end as usize)))
+    }
+}
+
+fn slice_lines(content: &str, start: usize, end: usize) -> Vec<&str> {
+    content
+        .lines()
+        .skip(start - 1)
+        .take(end + 1 - start)
+        .collect()
+}
+
+const DEFINITION_KEYWORDS: [&str; 10] = [
+    "fn", "def", "function", "func", "fun", "sub", "class", "struct", "enum", "trait",
+];
+
+// Languages where blocks are delimited by indentation rather than braces
+const INDENTED_EXTENSIONS: [&str; 4] = ["py", "pyi", "nim", "coffee"];
+
+// Finds the lines of a function, from its definition line to the end of its body. Definitions are
+// recognised by a keyword like fn or def before the name, or otherwise by an unindented line
+// calling the name like a C function. The body ends where the braces balance again, or for
+// indented languages and those without braces where the indentation returns to the level of the
+// definition.
+pub fn function_range(content: &str, name: &str, path: &Path) -> Option<(usize, usize)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let header = lines
+        .iter()
+        .position(|line| is_keyword_definition(line, name))
+        .or_else(|| lines.iter().position(|line| is_c_definition(line, name)))?;
+
+    let indented = path
+        .extension()
+        .map(|extension| INDENTED_EXTENSIONS.contains(&extension.to_string_lossy().as_ref()))
+        .unwrap_or(false);
+    let end = if indented {
+        None
+    } else {
+        brace_block_end(&lines, header)
+    }
+    .unwrap_or_else(|| indented_block_end(&lines, header));
+    Some((header + 1, end + 1))
+}
+
+fn is_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    ["//", "#", "/*", "*", "--", ";"]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+fn words(line: &str) -> impl Iterator<Item = &str> {
+    line.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .filter(|word| !word.is_empty())
+}
+
+fn is_keyword_definition(line: &str, name: &str) -> bool {
+    if is_comment(line) {
+        return false;
+    }
+    let words: Vec<&str> = words(line).collect();
+    words
+        .windows(2)
+        .any(|pair| pair[1] == name && DEFINITION_KEYWORDS.contains(&pair[0]))
+}
+
+fn is_c_definition(line: &str, name: &str) -> bool {
+    if line.starts_with(char::is_whitespace) || is_comment(line) || line.trim_end().ends_with(';') {
+        return false;
+    }
+    line.match_indices(name).any(|(index, _)| {
+        let before = line[..index].chars().last();
+        let after = line[index + name.len()..].trim_start();
+        !matches!(before, Some(c) if c.is_alphanumeric() || c == '_' || c == '.')
+            && after.starts_with('(')
+    })
+}
+
+// Returns the line where the braces opened after the header balance, or None if a statement ends
+// before any brace is opened
+fn brace_block_end(lines: &[&str], header: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut opened = false;
+    for (index, line) in lines.iter().enumerate().skip(header) {
+        // Braces in line comments and string literals are skipped
+        let code = line.split("//").next().unwrap_or_default();
+        let mut in_string = false;
+        let mut escaped = false;
+        for c in code.chars() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                '{' if !in_string => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' if !in_string => depth -= 1,
+                _ => {}
+            }
+        }
+        if opened && depth <= 0 {
+            return Some(index);
+        }
+        if !opened && code.trim_end().ends_with(';') {
+            return None;
+        }
+    }
+    None
+}
+
+// Returns the last line indented deeper than the header, including a closing keyword like end
+fn indented_block_end(lines: &[&str], header: usize) -> usize {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let header_indent = indent(lines[header]);
+    let mut end = header;
+    for (index, line) in lines.iter().enumerate().skip(header + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indent(line) <= header_indent {
+            if line.trim() == "end" || line.trim_start().starts_with("end ") {
+                end = index;
+            }
+            break;
+        }
+        end = index;
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_a_range_past_hunks_above_it() {
+        // Two lines were added after line 2
+        assert_eq!(map_range(&[(2, 0, 3, 2)], 5, 6), (false, Some((3, 4))));
+        // A changed line further down is ignored
+        assert_eq!(map_range(&[(10, 1, 10, 1)], 1, 3), (false, Some((1, 3))));
+    }
+
+    #[test]
+    fn maps_a_rewritten_line_to_its_old_lines() {
+        assert_eq!(map_range(&[(5, 1, 5, 1)], 4, 6), (true, Some((4, 6))));
+        assert_eq!(map_range(&[(5, 3, 5, 1)], 5, 5), (true, Some((5, 7))));
+    }
+
+    #[test]
+    fn maps_added_lines_to_nothing() {
+        assert_eq!(map_range(&[(2, 0, 3, 2)], 3, 4), (true, None));
+    }
+
+    #[test]
+    fn counts_deleted_lines_inside_the_range() {
+        // Old lines 3 and 4 were deleted after new line 2
+        assert_eq!(map_range(&[(3, 2, 2, 0)], 2, 3), (true, Some((2, 5))));
+        assert_eq!(map_range(&[(3, 2, 2, 0)], 1, 2), (false, Some((1, 2))));
+    }
+
+    #[test]
+    fn finds_brace_delimited_functions() {
+        let content = "fn a() {\n    x\n}\n\n// fn b is below\nfn b() {\n    if y {\n    }\n}\n";
+        assert_eq!(
+            function_range(content, "b", Path::new("lib.rs")),
+            Some((6, 9))
+        );
+        assert_eq!(function_range(content, "c", Path::new("lib.rs")), None);
+    }
+
+    #[test]
+    fn finds_c_functions_without_a_keyword() {
+        let content = "static int\nmain(int argc)\n{\n  return 0;\n}\nint other(void);\n";
+        assert_eq!(
+            function_range(content, "main", Path::new("main.c")),
+            Some((2, 5))
+        );
+    }
+
+    #[test]
+    fn finds_indented_functions() {
+        let content = "def f():\n    return 1\n\ndef g():\n    pass\n";
+        assert_eq!(
+            function_range(content, "f", Path::new("f.py")),
+            Some((1, 2))
+        );
+        let content = "def f\n  1\nend\n";
+        assert_eq!(
+            function_range(content, "f", Path::new("f.rb")),
+            Some((1, 3))
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+
+use git2::Repository;
+
+use crate::diff_settings::DiffSettings;
+use crate::model::{self, LocalChanges};
+
+// The local changes rows that have changes, each with a hash of its patch so that editing a file
+// again is noticed as well as a file starting or stopping to have changes
+pub type LocalState = Vec<(LocalChanges, u64)>;
+
+// Diffs the working tree and index on a thread of its own, since on a large working tree that
+// takes long enough to hold up the interface every time it's done
+#[derive(Default)]
+pub struct LocalChangesCheck {
+    running: Option<Receiver<LocalState>>,
+}
+
+impl LocalChangesCheck {
+    // Starts a check unless one is still running
+    pub fn start(&mut self, repository: &Repository, settings: &DiffSettings, paths: Vec<PathBuf>) {
+        if self.running.is_some() {
+            return;
+        }
+        let path = repository.path().to_path_buf();
+        let settings = settings.clone();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            if let Ok(repository) = Repository::open(path) {
+                let _ = sender.send(local_state(&repository, &settings, &paths));
+            }
+        });
+        self.running = Some(receiver);
+    }
+
+    // Returns what the check found once it's done
+    pub fn poll(&mut self) -> Option<LocalState> {
+        let state = match self.running.as_ref()?.try_recv() {
+            Ok(state) => Some(state),
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => None,
+        };
+        self.running = None;
+        state
+    }
+
+    // Forgets a running check, for when the local changes were just looked at some other way
+    pub fn cancel(&mut self) {
+        self.running = None;
+    }
+}
+
+// Rename detection is left out since it doesn't change whether there are changes or what they are
+pub fn local_state(
+    repository: &Repository,
+    settings: &DiffSettings,
+    paths: &[PathBuf],
+) -> LocalState {
+    [LocalChanges::Unstaged, LocalChanges::Staged]
+        .iter()
+        .filter_map(|local| {
+            let diff = local_diff(repository, settings, *local).ok()?;
+            patch_hash(&diff, paths).map(|hash| (*local, hash))
+        })
+        .collect()
+}
+
+fn local_diff<'r>(
+    repository: &'r Repository,
+    settings: &DiffSettings,
+    local: LocalChanges,
+) -> Result<git2::Diff<'r>, git2::Error> {
+    match local {
+        LocalChanges::Unstaged => {
+            repository.diff_index_to_workdir(None, Some(&mut settings.options()))
+        }
+        LocalChanges::Staged => {
+            let head = repository.head()?.peel_to_tree()?;
+            repository.diff_tree_to_index(Some(&head), None, Some(&mut settings.options()))
+        }
+    }
+}
+
+// A hash of the patch of the files under `paths`, or of every file when there are none. None when
+// no file under them changed.
+fn patch_hash(diff: &git2::Diff, paths: &[PathBuf]) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    let mut changed = false;
+    diff.print(git2::DiffFormat::Patch, |delta, _, line| {
+        let shown = paths.is_empty()
+            || paths.iter().any(|path| {
+                model::diff_file_starts_with(&delta.old_file(), path)
+                    || model::diff_file_starts_with(&delta.new_file(), path)
+            });
+        if shown {
+            changed = true;
+            line.origin().hash(&mut hasher);
+            line.content().hash(&mut hasher);
+        }
+        true
+    })
+    .ok()?;
+    Some(hasher.finish()).filter(|_| changed)
+}
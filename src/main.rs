@@ -1,19 +1,41 @@
-use chrono::offset::TimeZone;
-use std::{collections::HashSet, path::Path, str::FromStr, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    io::Write,
+    path::Path,
+    str::FromStr,
+    time::Instant,
+};
 
+mod ansi;
+mod clipboard;
+mod config;
 mod controller;
+mod diff_settings;
+mod external;
+mod format;
+mod glob;
+mod graph;
+mod highlight;
+mod html;
 mod instrument;
+mod json;
+mod keymap;
+mod line_range;
+mod local;
+mod mbox;
 mod model;
+mod syntax;
+mod terminal;
+mod theme;
+mod tree;
 mod widgets;
+mod word_diff;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let start_time = Instant::now();
     let app = app_args();
     let matches = app.get_matches_from(std::env::args_os());
-    let is_verbose = matches.is_present("verbose");
-    if is_verbose {
-        dbg!(&matches);
-    }
 
     let repository_dir = matches
         .value_of("working-directory")
@@ -22,89 +44,238 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         // otherwise use the current dir
         .or_else(|| Some(std::env::current_dir().map_err(|e| format!("{}", e))))
         .expect("Missing value AND default for working-directory")?;
-    let revision = matches.value_of("COMMITTISH").map(|s| s.to_string());
 
     // TODO: re-use this in the path filter creation
     let repository = git2::Repository::discover(&repository_dir)?;
 
-    let filters: Vec<_> = matches
+    // Flags from the args setting are parsed as if they came before the ones given to gitt
+    let config = config::Config::load(&repository)?;
+    let matches = if config.args.is_empty() {
+        matches
+    } else {
+        let mut args = std::env::args_os();
+        app_args().get_matches_from(
+            args.next()
+                .into_iter()
+                .chain(config.args.iter().map(OsString::from))
+                .chain(args),
+        )
+    };
+    let is_verbose = matches.is_present("verbose");
+    if is_verbose {
+        dbg!(&matches);
+    }
+    let revision = matches.value_of("COMMITTISH").map(|s| s.to_string());
+
+    if let Some(export) = matches.subcommand_matches("format-patch") {
+        return format_patch(&repository, export);
+    }
+    if let Some(revspec) = &revision {
+        repository.revparse(revspec)?;
+    }
+
+    let mut filters: Vec<_> = matches
         .values_of("path")
         .map(|paths| {
             paths
                 .map(|path| {
                     let path = Path::new(path).to_path_buf();
 
-                    let repository = git2::Repository::discover(&repository_dir).unwrap();
-                    let model = model::AppModel::new(
-                        model::AppState::Commits,
-                        repository,
-                        revision.clone(),
-                        Vec::new(),
-                    )
-                    .unwrap();
-
-                    let repository = git2::Repository::discover(&repository_dir).unwrap();
-
-                    let ids: HashSet<git2::Oid> = model
-                        .walker()
-                        .flat_map(|commit| {
-                            let tree = commit.tree().ok();
-                            let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
-
-                            let parent_path_tree = parent_tree.as_ref().and_then(|t| {
-                                path.parent()
-                                    .and_then(|p| t.get_path(p).ok().map(|t_p| t_p.id()))
-                            });
-                            let path_tree = tree.as_ref().and_then(|t| {
-                                path.parent()
-                                    .and_then(|p| t.get_path(p).ok().map(|t_p| t_p.id()))
-                            });
-                            // If the tree ids are the same then they must not match
-                            if path_tree != None && parent_path_tree == path_tree {
-                                None
-                            } else {
-                                let diff = repository
-                                    .diff_tree_to_tree(parent_tree.as_ref(), tree.as_ref(), None)
-                                    .expect("Unable to create diff");
-                                let matches = diff.deltas().any(|delta| {
-                                    let old_file_matches = model::diff_file_starts_with(
-                                        &delta.old_file(),
-                                        path.as_path(),
-                                    );
-                                    let new_file_matches = model::diff_file_starts_with(
-                                        &delta.new_file(),
-                                        path.as_path(),
-                                    );
-                                    old_file_matches || new_file_matches
-                                });
+                    let ids: HashSet<git2::Oid> =
+                        model::CommitView::new(&repository, revision.as_ref(), &[])
+                            .flat_map(|commit| {
+                                let tree = commit.tree().ok();
+                                let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
 
-                                if matches {
-                                    Some(commit.id())
-                                } else {
+                                let parent_path_tree = parent_tree.as_ref().and_then(|t| {
+                                    path.parent()
+                                        .and_then(|p| t.get_path(p).ok().map(|t_p| t_p.id()))
+                                });
+                                let path_tree = tree.as_ref().and_then(|t| {
+                                    path.parent()
+                                        .and_then(|p| t.get_path(p).ok().map(|t_p| t_p.id()))
+                                });
+                                // If the tree ids are the same then they must not match
+                                if path_tree.is_some() && parent_path_tree == path_tree {
                                     None
+                                } else {
+                                    let diff = repository
+                                        .diff_tree_to_tree(
+                                            parent_tree.as_ref(),
+                                            tree.as_ref(),
+                                            None,
+                                        )
+                                        .expect("Unable to create diff");
+                                    let matches = diff.deltas().any(|delta| {
+                                        let old_file_matches = model::diff_file_starts_with(
+                                            &delta.old_file(),
+                                            path.as_path(),
+                                        );
+                                        let new_file_matches = model::diff_file_starts_with(
+                                            &delta.new_file(),
+                                            path.as_path(),
+                                        );
+                                        old_file_matches || new_file_matches
+                                    });
+
+                                    if matches {
+                                        Some(commit.id())
+                                    } else {
+                                        None
+                                    }
                                 }
-                            }
-                        })
-                        .collect();
+                            })
+                            .collect();
                     if is_verbose {
-                        println!("Identified {} commits that match the path ({}b)", ids.len(), ids.len() * std::mem::size_of::<git2::Oid>());
+                        println!(
+                            "Identified {} commits that match the path ({}b)",
+                            ids.len(),
+                            ids.len() * std::mem::size_of::<git2::Oid>()
+                        );
                     }
 
                     model::CommitFilter::Path((path, ids))
                 })
                 .collect()
         })
-        .unwrap_or_else(Vec::new);
+        .unwrap_or_default();
+
+    if let Some(ranges) = matches.values_of("line-range") {
+        // The lines are followed back from the commit being viewed, the included end of a range
+        let start = match &revision {
+            Some(revspec) => {
+                let revspec = repository.revparse(revspec)?;
+                revspec
+                    .to()
+                    .or_else(|| revspec.from())
+                    .ok_or("revision specifier not converted into oid")?
+                    .peel_to_commit()?
+                    .id()
+            }
+            None => repository.head()?.peel_to_commit()?.id(),
+        };
+        for range in ranges {
+            let spec: line_range::LineRangeSpec = range.parse()?;
+            let history = line_range::LineHistory::new(&repository, start, spec)
+                .map_err(|e| e.message().to_string())?;
+            if is_verbose {
+                println!(
+                    "Identified {} commits that changed the lines",
+                    history.commits.len()
+                );
+            }
+            filters.push(model::CommitFilter::Lines(history));
+        }
+    }
+
+    let diff_settings = diff_settings(&config, &matches)?;
+
+    // Printing only walks the commits, so the interface and its first diff are never set up
+    let printing = matches.is_present("print") || matches.is_present("format");
+    if printing
+        && !["pick", "json", "html"]
+            .iter()
+            .any(|arg| matches.is_present(arg))
+    {
+        let template = matches.value_of("format").unwrap_or("%h%d %s");
+        let stdout = std::io::stdout();
+        let mut output = std::io::BufWriter::new(stdout.lock());
+        let printed = format::print_commits(
+            &mut output,
+            &repository,
+            model::CommitView::new(&repository, revision.as_ref(), &filters),
+            template,
+            matches.is_present("graph"),
+        )
+        .and_then(|_| Ok(output.flush()?));
+        return ignore_broken_pipe(printed);
+    }
+
+    // Exports without --pick don't need the interface either, only the commits and their diffs
+    if let Some(detail) = matches.value_of("json") {
+        let stdout = std::io::stdout();
+        let mut output = std::io::BufWriter::new(stdout.lock());
+        let written = json::write_commits(
+            &mut output,
+            &repository,
+            &diff_settings,
+            &filters,
+            model::CommitView::new(&repository, revision.as_ref(), &filters),
+            detail.parse()?,
+        )
+        .and_then(|_| Ok(output.flush()?));
+        return ignore_broken_pipe(written);
+    }
+    if let (Some(path), false) = (matches.value_of("html"), matches.is_present("pick")) {
+        return write_html(
+            Path::new(path),
+            &repository,
+            model::CommitView::new(&repository, revision.as_ref(), &filters),
+            config.date_format.as_deref(),
+            &HashMap::new(),
+        );
+    }
 
     let mut app_model =
         model::AppModel::new(model::AppState::Commits, repository, revision, filters)?;
+    app_model.set_theme(config.theme);
+    app_model.set_date_format(config.date_format.clone());
+    app_model.set_difftool(
+        config.difftool.clone(),
+        config.difftool_capture.unwrap_or(false),
+    );
+    app_model.set_diff_settings(diff_settings);
+    // Files matching these start collapsed, from the collapse setting and the command line
+    let mut collapse_globs = config.collapse.clone();
+    if let Some(globs) = matches.values_of("collapse") {
+        collapse_globs.extend(globs.map(|glob| glob.to_string()));
+    }
+    app_model.set_collapse_globs(collapse_globs);
+    if matches.is_present("no-syntax-highlighting") || config.syntax_highlighting == Some(false) {
+        app_model.set_syntax_highlighting(false);
+    }
+    if let Some(mode) = config.word_diff {
+        app_model.set_word_diff(mode);
+    }
+    if let Some(mode) = matches.value_of("word-diff") {
+        app_model.set_word_diff(mode.parse()?);
+    }
+    // The highlighter setting is used from the start, while the filter for git add -p and git's
+    // pager are only used with --highlighter or once H turns them on
+    let git_config = app_model.repository().config()?;
+    let configured = config.highlighter.clone();
+    let highlighter = matches
+        .value_of("highlighter")
+        .map(|command| command.to_string())
+        .or_else(|| configured.clone())
+        .or_else(|| git_config.get_string("interactive.diffFilter").ok())
+        .or_else(|| {
+            std::env::var("GIT_PAGER")
+                .ok()
+                .or_else(|| git_config.get_string("core.pager").ok())
+                // Pagers that only page would show the patch as gitt already does
+                .filter(|pager| {
+                    !matches!(
+                        pager.split_whitespace().next(),
+                        None | Some("less") | Some("more") | Some("cat")
+                    )
+                })
+        })
+        .filter(|command| !command.trim().is_empty());
+    app_model.set_highlighter(
+        highlighter,
+        matches.is_present("highlighter") || configured.is_some(),
+    );
+    if matches.is_present("pick") {
+        app_model.start_picking();
+    }
 
-    let tick_rate = std::time::Duration::from_millis(200);
-    let mut handler = controller::EventHandler::new(tick_rate);
+    let mut handler = controller::EventHandler::new(config.tick_rate(), config.keys.clone());
 
     let bounds: Vec<_> = (0..6).map(|_| cassowary::Variable::new()).collect();
     let window_width = cassowary::Variable::new();
-    let mut column_solver = widgets::commit_list_column_width_solver(&bounds, &window_width);
+    let mut column_solver =
+        widgets::commit_list_column_width_solver(&bounds, &window_width, &config.column_layout());
 
     if is_verbose {
         println!("gitt startup took: {:?}", start_time.elapsed());
@@ -114,10 +285,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut peak_update = instrument::Timing::new("peak update time".to_string());
 
     // TODO: use RAII for this somehow
-    crossterm::execute!(std::io::stdout(), crossterm::terminal::EnterAlternateScreen)?;
+    crossterm::execute!(
+        terminal::output(),
+        crossterm::terminal::EnterAlternateScreen
+    )?;
     crossterm::terminal::enable_raw_mode().expect("can run in raw mode");
-    let stdout = std::io::stdout();
-    let backend = tui::backend::CrosstermBackend::new(stdout);
+    let backend = tui::backend::CrosstermBackend::new(terminal::output());
     let mut terminal = tui::Terminal::new(backend)?;
     terminal.clear()?;
 
@@ -139,26 +312,52 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .split(size);
 
             let chunk_commit = chunks[0];
+            let chunk_status = chunks[1];
             let chunk_details = chunks[2];
+            let files_width = if app_model.tree().is_some() {
+                std::cmp::min(60, chunk_details.width / 3)
+            } else if app_model.diff_files().is_empty() {
+                0
+            } else {
+                std::cmp::min(40, chunk_details.width / 4)
+            };
             let chunk_details = tui::layout::Layout::default()
                 .direction(tui::layout::Direction::Horizontal)
                 .constraints(
                     [
+                        tui::layout::Constraint::Length(files_width),
                         tui::layout::Constraint::Min(10),
                         tui::layout::Constraint::Length(1),
                     ]
                     .as_ref(),
                 )
                 .split(chunk_details);
-            let chunk_details_pane = chunk_details[0];
-            let chunk_details_scroll = chunk_details[1];
+            let chunk_files = chunk_details[0];
+            let chunk_details_pane = chunk_details[1];
+            let chunk_details_scroll = chunk_details[2];
             let commits_block = tui::widgets::Block::default();
             let details_block = tui::widgets::Block::default();
 
             app_model.resize_revision_window(commits_block.inner(chunk_commit).height as usize);
-            let commit_items: Vec<_> = app_model.commits().iter().map(commit_list_item).collect();
+            let marked = app_model.marked();
+            let theme = *app_model.theme();
+            let commit_items: Vec<_> = app_model
+                .revisions()
+                .iter()
+                .map(|revision| match revision {
+                    model::Revision::Commit(commit) => commit_list_item(
+                        commit,
+                        marked,
+                        app_model.is_picked(commit.id()),
+                        app_model.date_format(),
+                        &theme,
+                    ),
+                    model::Revision::Local(local) => local_changes_list_item(*local, &theme),
+                })
+                .collect();
 
-            app_model.resize_diff_window(details_block.inner(chunk_details_pane).height as usize);
+            let details_area = details_block.inner(chunk_details_pane);
+            app_model.resize_diff_window(details_area.height as usize, details_area.width as usize);
 
             // TODO: https://github.com/fdehau/tui-rs/issues/499
             column_solver
@@ -180,19 +379,76 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 total_length: details_length,
                 style: tui::style::Style::default().bg(
                     if app_model.app_state == model::AppState::Details {
-                        tui::style::Color::Gray
+                        theme.scrollbar
                     } else {
                         tui::style::Color::Black
                     },
                 ),
             };
-            let details_block = tui::widgets::Paragraph::new(app_model.diff())
-                .scroll((details_index as u16, 0))
-                .block(details_block);
+            let details_block =
+                tui::widgets::Paragraph::new(app_model.diff_window()).block(details_block);
+
+            let file_items: Vec<_> = app_model
+                .diff_files()
+                .iter()
+                .map(|file| file_list_item(file, &theme))
+                .collect();
+            let mut files_state = tui::widgets::ListState::default();
+            files_state.select(app_model.file_index());
+            let files = tui::widgets::List::new(file_items)
+                .block(tui::widgets::Block::default().borders(tui::widgets::Borders::RIGHT))
+                .highlight_style(if app_model.app_state == model::AppState::Files {
+                    tui::style::Style::default()
+                        .add_modifier(tui::style::Modifier::BOLD | tui::style::Modifier::REVERSED)
+                } else {
+                    tui::style::Style::default().add_modifier(tui::style::Modifier::BOLD)
+                });
 
             let (list_state, _) = app_model.revision_window();
             rect.render_stateful_widget(list, chunk_commit, &mut list_state.clone());
+            if let Some(tree) = app_model.tree() {
+                let tree_items: Vec<_> = tree
+                    .rows
+                    .iter()
+                    .map(|row| tree_row_item(row, &theme))
+                    .collect();
+                let mut tree_state = tui::widgets::TableState::default();
+                tree_state.select(Some(tree.selected));
+                let tree_widths = [
+                    tui::layout::Constraint::Length(6),
+                    tui::layout::Constraint::Length(6),
+                    tui::layout::Constraint::Length(chunk_files.width.saturating_sub(15)),
+                ];
+                let tree_table = tui::widgets::Table::new(tree_items)
+                    .block(tui::widgets::Block::default().borders(tui::widgets::Borders::RIGHT))
+                    .widths(&tree_widths)
+                    .highlight_style(if app_model.app_state == model::AppState::Tree {
+                        tui::style::Style::default().add_modifier(
+                            tui::style::Modifier::BOLD | tui::style::Modifier::REVERSED,
+                        )
+                    } else {
+                        tui::style::Style::default().add_modifier(tui::style::Modifier::BOLD)
+                    });
+                rect.render_stateful_widget(tree_table, chunk_files, &mut tree_state);
+            } else {
+                rect.render_stateful_widget(files, chunk_files, &mut files_state);
+            }
             rect.render_widget(details_block, chunk_details_pane);
+            let status = match (app_model.status(), app_model.pick_count()) {
+                (Some(status), _) => Some(status.to_string()),
+                (None, Some(count)) => Some(format!(
+                    "{} picked: space picks the selected commit, enter finishes, q cancels",
+                    count
+                )),
+                (None, None) => None,
+            };
+            if let Some(status) = status {
+                rect.render_widget(
+                    tui::widgets::Paragraph::new(status)
+                        .style(tui::style::Style::default().fg(theme.status)),
+                    chunk_status,
+                );
+            }
             rect.render_widget(details_scroll, chunk_details_scroll);
         })?;
 
@@ -204,7 +460,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         {
             crossterm::terminal::disable_raw_mode()?;
             terminal.show_cursor()?;
-            crossterm::execute!(std::io::stdout(), crossterm::terminal::LeaveAlternateScreen)?;
+            crossterm::execute!(
+                terminal::output(),
+                crossterm::terminal::LeaveAlternateScreen
+            )?;
+            // Picked commits are printed once the terminal is back to normal, or exported with
+            // their details when writing HTML
+            let picked = app_model.picked();
+            if let Some(path) = matches.value_of("html") {
+                if picked.is_empty() {
+                    return Err(format!("No commits were picked, {} was not written", path).into());
+                }
+                let details = picked
+                    .iter()
+                    .map(|commit| {
+                        let lines = app_model.commit_details(commit, html::DETAILS_WIDTH);
+                        (commit.id(), lines)
+                    })
+                    .collect();
+                write_html(
+                    Path::new(path),
+                    app_model.repository(),
+                    app_model.walker(),
+                    app_model.date_format(),
+                    &details,
+                )?;
+            } else {
+                let template = matches.value_of("format").unwrap_or("%H");
+                let decorations = format::Decorations::new(app_model.repository())?;
+                for commit in picked {
+                    let refs = decorations.get(commit.id());
+                    println!("{}", format::format_commit(template, &commit, refs));
+                }
+            }
             if is_verbose {
                 println!("Quitting at index {}", app_model.revision_index());
                 println!("{}", peak_draw);
@@ -213,13 +501,152 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             break;
         }
 
+        // Programs like editors get the terminal to themselves until they exit, programs with
+        // captured output run while the interface waits
+        if let Some(command) = app_model.take_command() {
+            let name = command.name.clone();
+            let repository = app_model.repository();
+            let directory = repository
+                .workdir()
+                .unwrap_or_else(|| repository.path())
+                .to_path_buf();
+            let result = if command.capture {
+                command.run(&directory)
+            } else {
+                handler.suspend();
+                crossterm::terminal::disable_raw_mode()?;
+                crossterm::execute!(
+                    terminal::output(),
+                    crossterm::terminal::LeaveAlternateScreen
+                )?;
+                terminal.show_cursor()?;
+                let result = command.run(&directory);
+                crossterm::execute!(
+                    terminal::output(),
+                    crossterm::terminal::EnterAlternateScreen
+                )?;
+                crossterm::terminal::enable_raw_mode()?;
+                terminal.clear()?;
+                handler.resume();
+                result
+            };
+            app_model.command_finished(&name, result);
+        }
+
         peak_update.record_max(update_start, app_model.revision_index());
     }
     Ok(())
 }
 
-fn commit_list_item(commit: &git2::Commit) -> tui::widgets::Row<'static> {
-    let time = format_time(&commit.time());
+// The diff options from the config with the command line flags applied over them
+fn diff_settings(
+    config: &config::Config,
+    matches: &clap::ArgMatches,
+) -> Result<diff_settings::DiffSettings, String> {
+    let mut settings = config.diff_settings();
+    if let Some(lines) = matches.value_of("unified") {
+        settings.context_lines = lines
+            .parse()
+            .map_err(|e| format!("Invalid number of context lines: {}", e))?;
+    }
+    if matches.is_present("ignore-all-space") {
+        settings.whitespace = diff_settings::Whitespace::IgnoreAll;
+    } else if matches.is_present("ignore-space-change") {
+        settings.whitespace = diff_settings::Whitespace::IgnoreChange;
+    } else if matches.is_present("ignore-space-at-eol") {
+        settings.whitespace = diff_settings::Whitespace::IgnoreAtEol;
+    }
+    if let Some(algorithm) = matches.value_of("diff-algorithm") {
+        settings.algorithm = algorithm.parse()?;
+    }
+    if matches.is_present("indent-heuristic") {
+        settings.indent_heuristic = true;
+    } else if matches.is_present("no-indent-heuristic") {
+        settings.indent_heuristic = false;
+    }
+    if matches.is_present("no-renames") {
+        settings.renames = false;
+    }
+    if matches.is_present("find-copies") {
+        settings.copies = true;
+    }
+    if let Some(threshold) = matches.value_of("similarity") {
+        settings.similarity_threshold = threshold
+            .parse()
+            .ok()
+            .filter(|threshold| *threshold <= 100)
+            .ok_or_else(|| format!("Invalid similarity percentage: {}", threshold))?;
+    }
+    Ok(settings)
+}
+
+fn write_html(
+    path: &Path,
+    repository: &git2::Repository,
+    commits: model::CommitView,
+    date_format: Option<&str>,
+    details: &HashMap<git2::Oid, Vec<tui::text::Spans<'static>>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut output = std::io::BufWriter::new(std::fs::File::create(path)?);
+    html::write_page(&mut output, repository, commits, date_format, details)?;
+    output.flush()?;
+    Ok(())
+}
+
+fn format_patch(
+    repository: &git2::Repository,
+    matches: &clap::ArgMatches,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let commits = match matches.value_of("RANGE") {
+        Some(range) => mbox::parse_range(repository, range)?,
+        // Like git format-patch -1
+        None => {
+            let head = repository.head()?.peel_to_commit()?;
+            mbox::inclusive_range(repository, &head, &head)?
+        }
+    };
+    let patches = mbox::format_patches(repository, &commits)?;
+    if matches.is_present("stdout") {
+        let stdout = std::io::stdout();
+        let mut output = std::io::BufWriter::new(stdout.lock());
+        let written = patches
+            .iter()
+            .try_for_each(|patch| output.write_all(patch))
+            .and_then(|_| output.flush());
+        return ignore_broken_pipe(written.map_err(|error| error.into()));
+    }
+    let directory = Path::new(matches.value_of("output-directory").unwrap_or("."));
+    for path in mbox::write_patches(directory, &commits, &patches)? {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+// Output piped to something like head can stop being read at any point
+fn ignore_broken_pipe(
+    result: Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match result {
+        Err(error)
+            if error
+                .downcast_ref::<std::io::Error>()
+                .map(|error| error.kind())
+                == Some(std::io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn commit_list_item(
+    commit: &git2::Commit,
+    marked: Option<git2::Oid>,
+    picked: bool,
+    date_format: Option<&str>,
+    theme: &theme::Theme,
+) -> tui::widgets::Row<'static> {
+    let time = format::format_time(&commit.time(), date_format);
     // TODO: If this needs to be length limited include unicode_segmentation
     let title = commit
         .message()
@@ -229,19 +656,80 @@ fn commit_list_item(commit: &git2::Commit) -> tui::widgets::Row<'static> {
         .expect("message body was bad")
         .to_owned();
     let author = commit.author().to_string();
-    tui::widgets::Row::new(vec![title, author, time])
+    let row = tui::widgets::Row::new(vec![title, author, time]);
+    if picked {
+        row.style(
+            tui::style::Style::default()
+                .fg(theme.picked)
+                .add_modifier(tui::style::Modifier::BOLD),
+        )
+    } else if marked == Some(commit.id()) {
+        row.style(tui::style::Style::default().fg(theme.marked))
+    } else {
+        row
+    }
+}
+
+// Styled after gitk, which shows unstaged changes in red and staged changes in green
+fn local_changes_list_item(
+    local: model::LocalChanges,
+    theme: &theme::Theme,
+) -> tui::widgets::Row<'static> {
+    let color = match local {
+        model::LocalChanges::Unstaged => theme.unstaged,
+        model::LocalChanges::Staged => theme.staged,
+    };
+    tui::widgets::Row::new(vec![local.description(), "", ""])
+        .style(tui::style::Style::default().fg(color))
 }
 
-fn format_time(time: &git2::Time) -> String {
-    let tz = chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
-        .expect("timezone offset was too big");
-    let dt = tz.timestamp(time.seconds(), 0);
-    dt.to_rfc3339()
+fn file_list_item(
+    file: &model::DiffFileEntry,
+    theme: &theme::Theme,
+) -> tui::widgets::ListItem<'static> {
+    tui::widgets::ListItem::new(tui::text::Spans::from(vec![
+        tui::text::Span::raw(format!("{} ", file.status)),
+        tui::text::Span::styled(
+            format!("+{:<4}", file.additions),
+            tui::style::Style::default().fg(theme.added),
+        ),
+        tui::text::Span::styled(
+            format!("-{:<4}", file.deletions),
+            tui::style::Style::default().fg(theme.removed),
+        ),
+        tui::text::Span::raw(file.path.to_string_lossy().to_string()),
+    ]))
+}
+
+fn tree_row_item(row: &tree::TreeRow, theme: &theme::Theme) -> tui::widgets::Row<'static> {
+    let indent = "  ".repeat(row.depth);
+    let name = if row.is_directory() {
+        let marker = if row.expanded { "▾" } else { "▸" };
+        tui::text::Span::styled(
+            format!("{}{} {}/", indent, marker, row.name),
+            tui::style::Style::default().fg(theme.directory),
+        )
+    } else {
+        tui::text::Span::raw(format!("{}  {}", indent, row.name))
+    };
+    tui::widgets::Row::new(vec![
+        tui::widgets::Cell::from(tui::text::Span::styled(
+            format!("{:06o}", row.mode),
+            tui::style::Style::default().fg(theme.muted),
+        )),
+        tui::widgets::Cell::from(format!(
+            "{:>6}",
+            row.size.map(tree::format_size).unwrap_or_default()
+        )),
+        tui::widgets::Cell::from(name),
+    ])
 }
 
 fn app_args() -> clap::Command<'static> {
     clap::Command::new("gitt")
         .about("Git repository viewer in your terminal")
+        // Flags from the args setting can be given again on the command line
+        .args_override_self(true)
         .arg(
             clap::Arg::new("working-directory")
                 .long("working-directory")
@@ -255,7 +743,206 @@ fn app_args() -> clap::Command<'static> {
                 .takes_value(false)
                 .help("Emit processing messages"),
         )
-        .arg(clap::Arg::new("COMMITTISH").help("Git ref to view"))
+        .arg(
+            clap::Arg::new("word-diff")
+                .long("word-diff")
+                .value_name("MODE")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .default_missing_value("plain")
+                .possible_values(["none", "word", "char", "plain"])
+                .help("Highlight changed words or characters, or show changes inline with plain"),
+        )
+        .arg(
+            clap::Arg::new("unified")
+                .short('U')
+                .long("unified")
+                .value_name("N")
+                .takes_value(true)
+                .help("Show N lines of context around changes"),
+        )
+        .arg(
+            clap::Arg::new("ignore-all-space")
+                .short('w')
+                .long("ignore-all-space")
+                .takes_value(false)
+                .help("Ignore whitespace when comparing lines"),
+        )
+        .arg(
+            clap::Arg::new("ignore-space-change")
+                .short('b')
+                .long("ignore-space-change")
+                .takes_value(false)
+                .help("Ignore changes in amount of whitespace"),
+        )
+        .arg(
+            clap::Arg::new("ignore-space-at-eol")
+                .long("ignore-space-at-eol")
+                .takes_value(false)
+                .help("Ignore changes in whitespace at the end of lines"),
+        )
+        .arg(
+            clap::Arg::new("diff-algorithm")
+                .long("diff-algorithm")
+                .value_name("ALGORITHM")
+                .takes_value(true)
+                .help("Choose the diff algorithm: myers, minimal or patience"),
+        )
+        .arg(
+            clap::Arg::new("indent-heuristic")
+                .long("indent-heuristic")
+                .takes_value(false)
+                .help("Shift hunk boundaries to make diffs easier to read, the default")
+                .overrides_with("no-indent-heuristic"),
+        )
+        .arg(
+            clap::Arg::new("no-indent-heuristic")
+                .long("no-indent-heuristic")
+                .takes_value(false)
+                .help("Keep hunk boundaries where the diff algorithm puts them")
+                .overrides_with("indent-heuristic"),
+        )
+        .arg(
+            clap::Arg::new("no-renames")
+                .long("no-renames")
+                .takes_value(false)
+                .help("Show renamed files as a deletion and an addition"),
+        )
+        .arg(
+            clap::Arg::new("find-copies")
+                .short('C')
+                .long("find-copies")
+                .takes_value(false)
+                .help("Detect files copied from other modified files"),
+        )
+        .arg(
+            clap::Arg::new("similarity")
+                .long("similarity")
+                .value_name("PERCENT")
+                .takes_value(true)
+                .help("How similar files must be to count as renamed or copied (default 50)"),
+        )
+        .arg(
+            clap::Arg::new("collapse")
+                .long("collapse")
+                .value_name("GLOB")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help(
+                    "Start files matching GLOB collapsed in the diff, e.g. Cargo.lock or *.min.js",
+                ),
+        )
+        .arg(
+            clap::Arg::new("no-syntax-highlighting")
+                .long("no-syntax-highlighting")
+                .required(false)
+                .takes_value(false)
+                .help("Show diffs without highlighting the syntax of their files"),
+        )
+        .arg(
+            clap::Arg::new("highlighter")
+                .long("highlighter")
+                .value_name("COMMAND")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .help(
+                    "Pipe patches through COMMAND, like delta --color-only, defaulting to \
+                     interactive.diffFilter or the pager git uses",
+                ),
+        )
+        .arg(
+            clap::Arg::new("pick")
+                .long("pick")
+                .takes_value(false)
+                .help("Pick commits with space, finish with enter and print them when gitt exits"),
+        )
+        .arg(
+            clap::Arg::new("format")
+                .long("format")
+                .value_name("TEMPLATE")
+                .takes_value(true)
+                .help(
+                    "Print commits with a template like '%h %an %s', implies --print unless \
+                     picking, see the README for placeholders",
+                ),
+        )
+        .arg(
+            clap::Arg::new("print")
+                .long("print")
+                .takes_value(false)
+                .help("Print the commits with --format instead of starting the interface"),
+        )
+        .group(
+            clap::ArgGroup::new("printing")
+                .args(&["print", "format"])
+                .multiple(true),
+        )
+        .arg(
+            clap::Arg::new("json")
+                .long("json")
+                .value_name("DETAIL")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .default_missing_value("commits")
+                .possible_values(["commits", "stat", "patch"])
+                .conflicts_with_all(&["pick", "format"])
+                .help("Print the commits as JSON Lines, with the files or the full patch of each"),
+        )
+        .arg(
+            clap::Arg::new("html")
+                .long("html")
+                .value_name("FILE")
+                .takes_value(true)
+                .help(
+                    "Write the commits to an HTML page, with --pick the picked commits' diffs \
+                     are included",
+                ),
+        )
+        .arg(
+            clap::Arg::new("graph")
+                .long("graph")
+                .takes_value(false)
+                .requires("printing")
+                .conflicts_with_all(&["pick", "json", "html"])
+                .help("Draw the commit graph to the left of printed commits"),
+        )
+        .arg(
+            clap::Arg::new("line-range")
+                .short('L')
+                .value_name("START,END:FILE")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help(
+                    "Limit commits to the ones changing the lines START to END of FILE, or the \
+                     function NAME with :NAME:FILE",
+                ),
+        )
+        .subcommand(
+            clap::Command::new("format-patch")
+                .about("Write commits as patch files for git am, like git format-patch")
+                .arg(clap::Arg::new("RANGE").help(
+                    "The commits to write, a range like main..topic, or since a commit like \
+                     main for main..HEAD, the default is the HEAD commit",
+                ))
+                .arg(
+                    clap::Arg::new("output-directory")
+                        .short('o')
+                        .long("output-directory")
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .help("Write the patch files to DIR instead of the current directory"),
+                )
+                .arg(
+                    clap::Arg::new("stdout")
+                        .long("stdout")
+                        .takes_value(false)
+                        .help("Write the patches to stdout as a single mbox"),
+                ),
+        )
+        .arg(clap::Arg::new("COMMITTISH").help("Git ref to view, or a range like main..topic"))
         .arg(
             clap::Arg::new("path")
                 .multiple_values(true)
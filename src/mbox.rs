@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+
+use git2::{Commit, Oid, Repository, Sort};
+
+// The commits of a range, oldest first so they apply in order. Merges are left out like git
+// format-patch does, since their changes can't be applied as a single patch.
+pub fn range_commits<'a>(
+    repository: &'a Repository,
+    include: Oid,
+    exclude: &[Oid],
+) -> Result<Vec<Commit<'a>>, git2::Error> {
+    let mut walker = repository.revwalk()?;
+    walker.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    walker.push(include)?;
+    for id in exclude {
+        walker.hide(*id)?;
+    }
+    let mut commits = Vec::new();
+    for id in walker {
+        let commit = repository.find_commit(id?)?;
+        if commit.parent_count() <= 1 {
+            commits.push(commit);
+        }
+    }
+    Ok(commits)
+}
+
+// The commits from `old` to `new` including both ends, `old` has to be an ancestor of `new`
+pub fn inclusive_range<'a>(
+    repository: &'a Repository,
+    old: &Commit,
+    new: &Commit,
+) -> Result<Vec<Commit<'a>>, git2::Error> {
+    range_commits(repository, new.id(), &old.parent_ids().collect::<Vec<_>>())
+}
+
+// The commits a revision names for format-patch, as git format-patch reads it: a range like
+// main..topic gives the commits of topic missing from main, and a single revision like main is
+// the same as main..HEAD
+pub fn parse_range<'a>(
+    repository: &'a Repository,
+    spec: &str,
+) -> Result<Vec<Commit<'a>>, git2::Error> {
+    let revspec = repository.revparse(spec)?;
+    let head = || Ok(repository.head()?.peel_to_commit()?.id());
+    let commit_id = |object: Option<&git2::Object>| match object {
+        Some(object) => object.peel_to_commit().map(|commit| commit.id()),
+        None => head(),
+    };
+    let from = commit_id(revspec.from())?;
+    let to = if revspec.mode().contains(git2::RevparseMode::RANGE) {
+        commit_id(revspec.to())?
+    } else {
+        head()?
+    };
+    range_commits(repository, to, &[from])
+}
+
+// Formats each commit as an email the way git format-patch does, with the From line that starts
+// an mbox message, the From, Date and Subject headers, the message, a diffstat and the patch
+pub fn format_patches(
+    repository: &Repository,
+    commits: &[Commit],
+) -> Result<Vec<Vec<u8>>, git2::Error> {
+    commits
+        .iter()
+        .enumerate()
+        .map(|(index, commit)| {
+            // libgit2 expects the subject to be the start of the message
+            if !commit
+                .message_bytes()
+                .starts_with(commit.summary_bytes().unwrap_or_default())
+            {
+                return Err(git2::Error::from_str(&format!(
+                    "The subject of {} spans several lines",
+                    commit.id()
+                )));
+            }
+            // The patch has to apply, so none of the viewing options like ignoring whitespace
+            // are used
+            let parent_tree = commit
+                .parent(0)
+                .ok()
+                .map(|parent| parent.tree())
+                .transpose()?;
+            let mut diff =
+                repository.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+            let email = diff.format_email(index + 1, commits.len(), commit, None)?;
+            Ok(email.to_vec())
+        })
+        .collect()
+}
+
+// Writes the patches as numbered files named after their subjects, like 0001-Fix-the-thing.patch
+pub fn write_patches(
+    directory: &Path,
+    commits: &[Commit],
+    patches: &[Vec<u8>],
+) -> std::io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(directory)?;
+    commits
+        .iter()
+        .zip(patches)
+        .enumerate()
+        .map(|(index, (commit, patch))| {
+            let path = directory.join(format!(
+                "{:04}-{}.patch",
+                index + 1,
+                file_name_subject(commit.summary().unwrap_or_default())
+            ));
+            std::fs::write(&path, patch)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+// Keeps letters, digits, dots and underscores and squeezes everything else into single dashes
+fn file_name_subject(subject: &str) -> String {
+    const MAX_LENGTH: usize = 52;
+    let mut name = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('-') {
+            name.push('-');
+        }
+        if name.len() >= MAX_LENGTH {
+            break;
+        }
+    }
+    name.trim_end_matches(['-', '.']).to_string()
+}
@@ -1,21 +1,45 @@
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use git2::{Commit, DiffFile, Oid, Repository};
-use tui::style::{Color, Style};
+use git2::{Commit, Delta, DiffDelta, DiffFile, ObjectType, Oid, Repository, Tree};
+use tui::style::Style;
 use tui::text::{Span, Spans};
 use tui::widgets::TableState;
 
+use crate::ansi;
+use crate::diff_settings::DiffSettings;
+use crate::external::{self, DiffFiles, DiffTool, ExternalCommand};
+use crate::format;
+use crate::glob;
+use crate::highlight::{HighlightedFile, Highlights, Patch};
+use crate::line_range::{LineHistory, LineRangeSpec, Lines, RangeAt};
+use crate::local::{self, LocalChangesCheck, LocalState};
+use crate::mbox;
+use crate::syntax::{HunkState, SyntaxHighlighter};
+use crate::theme::Theme;
+use crate::tree::{self, TreeBrowser};
+use crate::word_diff::{ChangeBuffer, WordDiffMode};
+
+// Blame dates are kept short unless a date-format is set, the full date would crowd out the lines
+const BLAME_DATE_FORMAT: &str = "%Y-%m-%d";
+// How often the working tree and index are checked for changes made outside of gitt
+const LOCAL_CHANGES_INTERVAL: Duration = Duration::from_secs(2);
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum AppState {
     Commits,
     Details,
+    Files,
+    Tree,
     Finished,
 }
 
-#[derive(PartialEq, Eq)]
 pub enum CommitFilter {
     Path((PathBuf, HashSet<Oid>)),
+    Lines(LineHistory),
+    #[allow(dead_code)]
     Text(String), // TODO: author? time?
 }
 
@@ -28,11 +52,33 @@ impl CommitFilter {
                 }
                 false
             }
+            Self::Lines(history) => history.commits.contains(&commit.id()),
             _ => unimplemented!(),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalChanges {
+    Unstaged,
+    Staged,
+}
+
+impl LocalChanges {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Unstaged => "Local uncommitted changes, not checked in to index",
+            Self::Staged => "Local changes checked in to index but not committed",
+        }
+    }
+}
+
+// A row in the list of commits, uncommitted changes are listed above the commits
+pub enum Revision<'a> {
+    Local(LocalChanges),
+    Commit(Commit<'a>),
+}
+
 pub struct CommitView<'a> {
     repository: &'a Repository,
     walker: Box<dyn Iterator<Item = Result<Oid, git2::Error>> + 'a>,
@@ -53,13 +99,25 @@ impl<'a> CommitView<'a> {
         let mut walker: git2::Revwalk<'a> =
             repository.revwalk().expect("Unable to initialize revwalk");
         if let Some(rev) = revision.as_ref() {
-            walker
-                .push(
-                    rev.from()
-                        .expect("revision specifier not converted into oid")
-                        .id(),
-                )
-                .expect("Unable to push ref onto revwalk");
+            let from = rev
+                .from()
+                .expect("revision specifier not converted into oid")
+                .id();
+            // A range lists the commits reachable from its end but not its start, like git log
+            match rev.to().map(|to| to.id()) {
+                Some(to) if rev.mode().contains(git2::RevparseMode::MERGE_BASE) => {
+                    walker.push(from).expect("Unable to push ref onto revwalk");
+                    walker.push(to).expect("Unable to push ref onto revwalk");
+                    if let Ok(base) = repository.merge_base(from, to) {
+                        walker.hide(base).expect("Unable to hide ref from revwalk");
+                    }
+                }
+                Some(to) => {
+                    walker.push(to).expect("Unable to push ref onto revwalk");
+                    walker.hide(from).expect("Unable to hide ref from revwalk");
+                }
+                None => walker.push(from).expect("Unable to push ref onto revwalk"),
+            }
         } else {
             walker
                 .push_head()
@@ -102,18 +160,775 @@ pub fn diff_file_starts_with(diff_file: &DiffFile, path: &Path) -> bool {
         .unwrap_or(false)
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffLineKind {
+    Message,
+    FileHeader,
+    HunkHeader,
+    Context,
+    Removed,
+    Added,
+    // A removed and added line merged together by the plain word diff
+    Merged,
+    // A line of the diffstat, its bar is drawn to fit the width of the window
+    Stat(usize),
+    Other,
+}
+
+impl DiffLineKind {
+    // Lines of a file's content rather than headers and summaries
+    fn is_content(self) -> bool {
+        matches!(
+            self,
+            DiffLineKind::Context
+                | DiffLineKind::Removed
+                | DiffLineKind::Added
+                | DiffLineKind::Merged
+        )
+    }
+}
+
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    // Index into the files of the diff this line belongs to
+    pub file: Option<usize>,
+    pub spans: Spans<'static>,
+}
+
+pub struct DiffFileEntry {
+    pub path: PathBuf,
+    // Single letter status in the style of git diff --name-status
+    pub status: char,
+    pub additions: usize,
+    pub deletions: usize,
+    // Index of the first line of the file header
+    pub line: usize,
+    // Collapsed files are shown as a single summary line in place of their header and hunks
+    pub collapsed: bool,
+}
+
+pub struct DiffStatEntry {
+    pub path: String,
+    pub additions: usize,
+    pub deletions: usize,
+    pub binary: bool,
+}
+
+// A file of a commit's diff as plain data, for exporting what the details pane shows
+pub struct PatchFile {
+    pub old_path: Option<PathBuf>,
+    pub new_path: Option<PathBuf>,
+    pub status: char,
+    pub stat: DiffStatEntry,
+    pub hunks: Vec<PatchHunk>,
+}
+
+pub struct PatchHunk {
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    // Each line with its origin, ' ', '+' or '-', and without its line ending
+    pub lines: Vec<(char, String)>,
+}
+
+// A diff turned into styled lines, built once per selected commit rather than on every draw
+#[derive(Default)]
+pub struct RenderedDiff {
+    pub lines: Vec<DiffLine>,
+    pub files: Vec<DiffFileEntry>,
+    pub stats: Vec<DiffStatEntry>,
+    // Indices of the hunk header lines
+    pub hunks: Vec<usize>,
+    // The commit each line of a blame came from, keyed by line index
+    pub blame: HashMap<usize, BlameOrigin>,
+    // Where the lines of hunks and of files shown in full start, keyed by the index of their first
+    // line
+    pub line_starts: HashMap<usize, LineStart>,
+    // The patch lines were styled by a diff highlighter, so syntax highlighting leaves them alone
+    pub highlighted: bool,
+    // Why the diff couldn't be built, for the status line
+    error: Option<String>,
+    current_file: Option<usize>,
+    syntax: RefCell<SyntaxCache>,
+}
+
+// Lines that were syntax highlighted, and where highlighting stopped with the state of the hunk
+// there, so scrolling on through a hunk doesn't parse it from the start again
+#[derive(Default)]
+struct SyntaxCache {
+    lines: HashMap<usize, Spans<'static>>,
+    resume: Option<(usize, HunkState)>,
+}
+
+pub struct LineStart {
+    pub path: PathBuf,
+    pub line: usize,
+    // The blob the lines come from, unknown for files in the working tree
+    pub blob: Option<Oid>,
+    // Deleted files only have lines on the old side of the diff
+    pub old_side: bool,
+}
+
+// A line of a file that a line of the diff belongs to
+pub struct FileLine {
+    pub path: PathBuf,
+    pub line: usize,
+    pub blob: Option<Oid>,
+}
+
+impl RenderedDiff {
+    fn start_file(&mut self, delta: Option<&DiffDelta>, collapsed: bool) {
+        let line = self.lines.len();
+        self.current_file = delta.map(|delta| {
+            self.files.push(DiffFileEntry {
+                path: delta
+                    .new_file()
+                    .path()
+                    .or_else(|| delta.old_file().path())
+                    .map(|p| p.to_path_buf())
+                    .unwrap_or_default(),
+                status: status_letter(delta.status()),
+                additions: 0,
+                deletions: 0,
+                line,
+                collapsed,
+            });
+            self.files.len() - 1
+        });
+    }
+
+    // Hunk headers can be jumped between, and the lines after them opened in an editor
+    fn start_hunk(&mut self, delta: &DiffDelta, hunk: Option<&git2::DiffHunk>) {
+        self.hunks.push(self.lines.len());
+        if let Some(hunk) = hunk {
+            // Lines are opened on the new side unless the file was deleted
+            let old_side = delta.status() == Delta::Deleted;
+            let (file, start) = if old_side {
+                (delta.old_file(), hunk.old_start())
+            } else {
+                (delta.new_file(), hunk.new_start())
+            };
+            if let Some(path) = file.path() {
+                self.line_starts.insert(
+                    self.lines.len() + 1,
+                    LineStart {
+                        path: path.to_path_buf(),
+                        line: start as usize,
+                        blob: Some(file.id()).filter(|id| !id.is_zero()),
+                        old_side,
+                    },
+                );
+            }
+        }
+    }
+
+    // Files shown in full rather than as a diff get an entry so their lines can be highlighted
+    fn start_blob(&mut self, path: &Path, blob: Oid) {
+        self.line_starts.insert(
+            self.lines.len(),
+            LineStart {
+                path: path.to_path_buf(),
+                line: 1,
+                blob: Some(blob),
+                old_side: false,
+            },
+        );
+        self.files.push(DiffFileEntry {
+            path: path.to_path_buf(),
+            status: ' ',
+            additions: 0,
+            deletions: 0,
+            line: self.lines.len(),
+            collapsed: false,
+        });
+        self.current_file = Some(self.files.len() - 1);
+    }
+
+    // Finds which line of which file a line of the diff is, counting from the start of its hunk.
+    // Headers of a file give the first line of the file's first hunk.
+    pub fn file_line(&self, index: usize) -> Option<FileLine> {
+        let file = self.lines.get(index)?.file?;
+        let (mut new_lines, mut old_lines) = (0, 0);
+        for current in (0..=index).rev() {
+            let line = &self.lines[current];
+            if current < index {
+                match line.kind {
+                    DiffLineKind::Context | DiffLineKind::Merged => {
+                        new_lines += 1;
+                        old_lines += 1;
+                    }
+                    DiffLineKind::Added => new_lines += 1,
+                    DiffLineKind::Removed => old_lines += 1,
+                    _ => {}
+                }
+            }
+            if let Some(start) = self.line_starts.get(&current) {
+                return Some(FileLine {
+                    path: start.path.clone(),
+                    line: start.line + if start.old_side { old_lines } else { new_lines },
+                    blob: start.blob,
+                });
+            }
+            if line.file != Some(file)
+                || matches!(
+                    line.kind,
+                    DiffLineKind::FileHeader | DiffLineKind::HunkHeader
+                )
+            {
+                break;
+            }
+        }
+        (index + 1..self.lines.len())
+            .take_while(|current| self.lines[*current].file == Some(file))
+            .find_map(|current| self.line_starts.get(&current))
+            .map(|start| FileLine {
+                path: start.path.clone(),
+                line: start.line,
+                blob: start.blob,
+            })
+    }
+
+    fn count_change(&mut self, origin: char) {
+        if let Some(file) = self.current_file.and_then(|file| self.files.get_mut(file)) {
+            match origin {
+                '+' => file.additions += 1,
+                '-' => file.deletions += 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn current_file_collapsed(&self) -> bool {
+        self.current_file
+            .and_then(|file| self.files.get(file))
+            .map(|file| file.collapsed)
+            .unwrap_or(false)
+    }
+
+    // The line counts are only known once the whole diff is printed, so the summaries of
+    // collapsed files are filled in afterwards
+    fn summarise_collapsed(&mut self, theme: &Theme) {
+        for file in self.files.iter().filter(|file| file.collapsed) {
+            self.lines[file.line].spans = Spans::from(vec![
+                Span::styled(
+                    format!("▸ {} {}", file.status, file.path.to_string_lossy()),
+                    Style::default().fg(theme.file_header),
+                ),
+                Span::styled(
+                    format!(" +{}", file.additions),
+                    Style::default().fg(theme.added),
+                ),
+                Span::styled(
+                    format!(" -{}", file.deletions),
+                    Style::default().fg(theme.removed),
+                ),
+                Span::styled(" (collapsed)", Style::default().fg(theme.muted)),
+            ]);
+        }
+    }
+
+    // Returns the file that the line at the given index belongs to
+    pub fn file_at(&self, line: usize) -> Option<usize> {
+        self.lines.get(line).and_then(|line| line.file)
+    }
+
+    // Lines are highlighted in the state the lines above them in their hunk leave, starting after
+    // the hunk header or the header of a file shown in full
+    fn syntax_line(
+        &self,
+        syntax: &SyntaxHighlighter,
+        index: usize,
+        theme: &Theme,
+    ) -> Spans<'static> {
+        let line = &self.lines[index];
+        let mut cache = self.syntax.borrow_mut();
+        if let Some(spans) = cache.lines.get(&index) {
+            return spans.clone();
+        }
+        let start = (0..index)
+            .rev()
+            .find(|&previous| !self.lines[previous].kind.is_content())
+            .map_or(0, |header| header + 1);
+        let (from, mut state) = match cache.resume.take() {
+            Some((from, state)) if from > start && from <= index => (from, state),
+            _ => match line
+                .file
+                .and_then(|file| syntax.start_hunk(&self.files[file].path))
+            {
+                Some(state) => (start, state),
+                None => return line.spans.clone(),
+            },
+        };
+        let mut spans = line.spans.clone();
+        for current in from..=index {
+            spans = syntax.highlight(&mut state, &self.lines[current], theme);
+            cache.lines.insert(current, spans.clone());
+        }
+        cache.resume = Some((index + 1, state));
+        spans
+    }
+
+    fn push(&mut self, kind: DiffLineKind, spans: Spans<'static>) {
+        self.lines.push(DiffLine {
+            kind,
+            file: self.current_file,
+            spans,
+        });
+    }
+
+    fn append(&mut self, lines: Vec<(DiffLineKind, Spans<'static>)>) {
+        lines
+            .into_iter()
+            .for_each(|(kind, spans)| self.push(kind, spans));
+    }
+}
+
+pub struct BlameOrigin {
+    pub commit: Oid,
+    // The path and line number of the line in that commit
+    pub path: PathBuf,
+    pub line: usize,
+}
+
+// The paths the filters limit diffs to
+fn filter_paths(filters: &[CommitFilter]) -> Vec<PathBuf> {
+    filters
+        .iter()
+        .flat_map(|filter| match filter {
+            CommitFilter::Path((path, _oids)) => Some(path.clone()),
+            CommitFilter::Lines(history) => Some(history.spec.path.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Where the lines followed by -L are in a commit
+fn line_ranges(filters: &[CommitFilter], commit: Oid) -> Vec<&RangeAt> {
+    filters
+        .iter()
+        .filter_map(|filter| match filter {
+            CommitFilter::Lines(history) => history.ranges.get(&commit),
+            _ => None,
+        })
+        .collect()
+}
+
+// The paths a commit's diff is limited to, both from path filters and lines followed by -L
+fn shown_paths(filters: &[CommitFilter], ranges: &[&RangeAt]) -> Vec<PathBuf> {
+    let mut paths = filter_paths(filters);
+    paths.extend(ranges.iter().map(|range| range.path.clone()));
+    paths
+}
+
+fn tree_diff<'r>(
+    repository: &'r Repository,
+    settings: &DiffSettings,
+    old: Option<&Tree>,
+    new: Option<&Tree>,
+) -> git2::Diff<'r> {
+    let mut git_diff = repository
+        .diff_tree_to_tree(old, new, Some(&mut settings.options()))
+        .expect("Unable to create diff");
+    if let Some(mut find_options) = settings.find_options() {
+        git_diff
+            .find_similar(Some(&mut find_options))
+            .expect("Unable to detect renames");
+    }
+    git_diff
+}
+
+// The diff of a commit against its parent, merges are not diffed
+fn commit_diff<'r>(
+    repository: &'r Repository,
+    settings: &DiffSettings,
+    commit: &Commit,
+) -> Option<git2::Diff<'r>> {
+    if commit.parents().len() > 1 {
+        return None;
+    }
+    let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+    Some(tree_diff(
+        repository,
+        settings,
+        parent_tree.as_ref(),
+        commit.tree().ok().as_ref(),
+    ))
+}
+
+// The files and hunks of a commit that the details pane shows, with the same diff settings and
+// filters. Exports use this without setting up the interface.
+pub fn patch_files(
+    repository: &Repository,
+    settings: &DiffSettings,
+    filters: &[CommitFilter],
+    commit: &Commit,
+) -> Vec<PatchFile> {
+    let git_diff = match commit_diff(repository, settings, commit) {
+        Some(git_diff) => git_diff,
+        None => return Vec::new(),
+    };
+    let ranges = line_ranges(filters, commit.id());
+    let paths = shown_paths(filters, &ranges);
+    let mut files = Vec::new();
+    for index in 0..git_diff.deltas().len() {
+        let delta = git_diff.get_delta(index).expect("delta index out of range");
+        if !shows_delta(&paths, &delta) {
+            continue;
+        }
+        let mut hunks = Vec::new();
+        let mut skipped = false;
+        if let Ok(Some(patch)) = git2::Patch::from_diff(&git_diff, index) {
+            for hunk_index in 0..patch.num_hunks() {
+                let (hunk, line_count) = patch.hunk(hunk_index).expect("hunk out of range");
+                if !shows_hunk(&ranges, &delta, &hunk) {
+                    skipped = true;
+                    continue;
+                }
+                let lines = (0..line_count)
+                    .filter_map(|line| patch.line_in_hunk(hunk_index, line).ok())
+                    .map(|line| {
+                        let content = String::from_utf8_lossy(line.content());
+                        (
+                            line.origin(),
+                            content.trim_end_matches(&['\n', '\r'][..]).to_string(),
+                        )
+                    })
+                    .collect();
+                hunks.push(PatchHunk {
+                    header: String::from_utf8_lossy(hunk.header())
+                        .trim_end()
+                        .to_string(),
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                    lines,
+                });
+            }
+        }
+        let mut stat = diff_stat_entry(&git_diff, index);
+        // Hunks outside of the lines followed by -L are left out, so are their changes
+        if skipped {
+            let count = |origin: char| {
+                hunks
+                    .iter()
+                    .flat_map(|hunk: &PatchHunk| hunk.lines.iter())
+                    .filter(|(line_origin, _)| *line_origin == origin)
+                    .count()
+            };
+            stat.additions = count('+');
+            stat.deletions = count('-');
+        }
+        files.push(PatchFile {
+            old_path: delta.old_file().path().map(Path::to_path_buf),
+            new_path: delta.new_file().path().map(Path::to_path_buf),
+            status: status_letter(delta.status()),
+            stat,
+            hunks,
+        });
+    }
+    files
+}
+
+// Single letter status in the style of git diff --name-status
+fn status_letter(status: Delta) -> char {
+    match status {
+        Delta::Added => 'A',
+        Delta::Deleted => 'D',
+        Delta::Modified => 'M',
+        Delta::Renamed => 'R',
+        Delta::Copied => 'C',
+        Delta::Typechange => 'T',
+        _ => '?',
+    }
+}
+
+// Whether the path filters let a file of a diff through
+fn shows_delta(paths: &[PathBuf], delta: &DiffDelta) -> bool {
+    paths.is_empty()
+        || paths.iter().any(|path| {
+            diff_file_starts_with(&delta.old_file(), path)
+                || diff_file_starts_with(&delta.new_file(), path)
+        })
+}
+
+// Whether a hunk overlaps the lines followed in its file, when any are
+fn shows_hunk(ranges: &[&RangeAt], delta: &DiffDelta, hunk: &git2::DiffHunk) -> bool {
+    let mut file_ranges = ranges
+        .iter()
+        .filter(|range| delta.new_file().path() == Some(range.path.as_path()))
+        .peekable();
+    file_ranges.peek().is_none()
+        || file_ranges
+            .any(|range| range.overlaps(hunk.new_start() as usize, hunk.new_lines() as usize))
+}
+
+fn diff_stat_entry(git_diff: &git2::Diff, index: usize) -> DiffStatEntry {
+    let delta = git_diff.get_delta(index).expect("delta index out of range");
+    let path = match (
+        delta.status(),
+        delta.old_file().path(),
+        delta.new_file().path(),
+    ) {
+        (Delta::Renamed, Some(old), Some(new)) | (Delta::Copied, Some(old), Some(new)) => {
+            format!("{} => {}", old.to_string_lossy(), new.to_string_lossy())
+        }
+        (_, old, new) => new
+            .or(old)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+    let patch = git2::Patch::from_diff(git_diff, index).expect("Unable to create patch");
+    let (binary, additions, deletions) = match patch {
+        Some(patch) => {
+            let (_, additions, deletions) =
+                patch.line_stats().expect("Unable to count patch lines");
+            (patch.delta().flags().is_binary(), additions, deletions)
+        }
+        None => (true, 0, 0),
+    };
+    DiffStatEntry {
+        path,
+        additions,
+        deletions,
+        binary,
+    }
+}
+
+// Draws a diffstat line with its bar scaled to fit the width like git does
+fn stat_line(stats: &[DiffStatEntry], index: usize, width: usize, theme: &Theme) -> Spans<'static> {
+    let stat = &stats[index];
+    let name_width = stats
+        .iter()
+        .map(|stat| stat.path.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(width / 2);
+    let max_changes = stats
+        .iter()
+        .map(|stat| stat.additions + stat.deletions)
+        .max()
+        .unwrap_or(0);
+    let count_width = max_changes.to_string().len();
+    let bar_width = width.saturating_sub(name_width + count_width + 4).max(1);
+
+    // Long paths are cut from the front to keep the file name visible
+    let length = stat.path.chars().count();
+    let name = if length > name_width {
+        let skipped: String = stat.path.chars().skip(length - name_width + 3).collect();
+        format!("...{}", skipped)
+    } else {
+        stat.path.clone()
+    };
+    if stat.binary {
+        return Spans::from(vec![Span::raw(format!(
+            " {:<name_width$} | {:>count_width$}",
+            name,
+            "Bin",
+            name_width = name_width,
+            count_width = count_width,
+        ))]);
+    }
+
+    let changes = stat.additions + stat.deletions;
+    let scale = |count: usize| {
+        if max_changes <= bar_width || count == 0 {
+            count
+        } else {
+            std::cmp::max(1, count * bar_width / max_changes)
+        }
+    };
+    Spans::from(vec![
+        Span::raw(format!(
+            " {:<name_width$} | {:>count_width$} ",
+            name,
+            changes,
+            name_width = name_width,
+            count_width = count_width,
+        )),
+        Span::styled(
+            "+".repeat(scale(stat.additions)),
+            Style::default().fg(theme.added),
+        ),
+        Span::styled(
+            "-".repeat(scale(stat.deletions)),
+            Style::default().fg(theme.removed),
+        ),
+    ])
+}
+
+pub fn short_id(commit: &Commit) -> String {
+    commit
+        .as_object()
+        .short_id()
+        .expect("Unable to write short_id")
+        .as_str()
+        .expect("short_id was not valid utf8")
+        .to_string()
+}
+
+// The lines of the patch text git would print for a line of a diff
+fn patch_lines(origin: char, content: &[u8]) -> Vec<(DiffLineKind, String)> {
+    let content = String::from_utf8_lossy(content);
+    let content = content.trim_end_matches(&['\n', '\r'][..]);
+    match origin {
+        'F' => content
+            .lines()
+            .map(|line| (DiffLineKind::FileHeader, line.to_string()))
+            .collect(),
+        'H' => vec![(DiffLineKind::HunkHeader, content.to_string())],
+        ' ' => vec![(DiffLineKind::Context, format!(" {}", content))],
+        '+' => vec![(DiffLineKind::Added, format!("+{}", content))],
+        '-' => vec![(DiffLineKind::Removed, format!("-{}", content))],
+        _ => content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| (DiffLineKind::Other, line.to_string()))
+            .collect(),
+    }
+}
+
+// Colors a patch line like git diff --color does by default, since highlighters expect to be
+// given git's colored output
+fn git_colored(kind: DiffLineKind, line: &str) -> String {
+    match kind {
+        DiffLineKind::FileHeader => format!("\x1b[1m{}\x1b[m", line),
+        DiffLineKind::HunkHeader => match line.get(2..).and_then(|rest| rest.find("@@")) {
+            Some(end) => format!("\x1b[36m{}\x1b[m{}", &line[..end + 4], &line[end + 4..]),
+            None => format!("\x1b[36m{}\x1b[m", line),
+        },
+        DiffLineKind::Added => format!("\x1b[32m{}\x1b[m", line),
+        DiffLineKind::Removed => format!("\x1b[31m{}\x1b[m", line),
+        _ => line.to_string(),
+    }
+}
+
+// Objects missing from the repository, like in a shallow clone, leave trees unreadable
+fn tree_error(error: &git2::Error) -> String {
+    format!("Unable to read the tree: {}", error.message())
+}
+
+// Renamed and copied files get a compact header naming both paths instead of the full git header
+fn file_header(delta: &DiffDelta, content: &str) -> Vec<String> {
+    let verb = match delta.status() {
+        Delta::Renamed => "rename",
+        Delta::Copied => "copy",
+        _ => {
+            return content
+                .split('\n')
+                .map(|s| s.trim_end().to_string())
+                .collect()
+        }
+    };
+    let similarity = content
+        .lines()
+        .find_map(|line| line.strip_prefix("similarity index "))
+        .unwrap_or("100%");
+    let path = |file: DiffFile| {
+        file.path()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    vec![
+        format!("{} from {}", verb, path(delta.old_file())),
+        format!("{} to {} ({})", verb, path(delta.new_file()), similarity),
+        String::new(),
+    ]
+}
+
+// Parts of the selection that can be copied to the clipboard
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Yank {
+    Id,
+    ShortId,
+    Subject,
+    // The style used to refer to commits in commit messages, like: 1234abc ("Fix the thing")
+    Reference,
+    Path,
+}
+
+// A file shown in full in place of the diff, either as the commit left it or as it was in the
+// commit's first parent
+pub struct FileView {
+    pub commit: Oid,
+    pub path: PathBuf,
+    pub parent: bool,
+}
+
+// A file annotated with the commit that last changed each line, as of `commit`. Digging into the
+// history moves `commit` back while `selected` stays the commit the blame was opened from.
+pub struct BlameView {
+    pub selected: Oid,
+    pub commit: Oid,
+    pub path: PathBuf,
+    // Line number to put the cursor on
+    line: usize,
+}
+
+// What a diff program printed, shown in place of the diff until another commit is selected
+pub struct ExternalOutput {
+    commit: Option<Oid>,
+    name: String,
+    lines: Vec<Spans<'static>>,
+}
+
 pub struct AppModel {
     pub app_state: AppState,
     repository: Repository,
     revspec: Option<String>,
     filters: Vec<CommitFilter>,
+    local_changes: Vec<LocalChanges>,
+    // The rows with a hash of their patches as of the last look, and when that was
+    local_state: LocalState,
+    local_changes_checked: Instant,
+    local_check: LocalChangesCheck,
     revision_index: usize,
     revision_window_index: TableState,
     revision_window_length: usize,
     revision_max: usize,
     diff_index: usize,
+    // Views where single lines can be acted on, like blame, have a cursor in the diff pane
+    diff_cursor: Option<usize>,
+    // The other end of a range of lines being selected in the file view
+    range_anchor: Option<usize>,
     diff_window_length: usize,
+    diff_window_width: usize,
     diff_length: usize,
+    file_index: Option<usize>,
+    diff: RenderedDiff,
+    // Files the user collapsed or expanded, kept while moving between commits
+    collapsed: HashMap<PathBuf, bool>,
+    collapse_globs: Vec<String>,
+    diff_settings: DiffSettings,
+    // A commit marked for comparison against the selected commit
+    marked: Option<Oid>,
+    compare_swapped: bool,
+    word_diff: WordDiffMode,
+    syntax: Option<SyntaxHighlighter>,
+    // A command like delta that patches are piped through, and whether it is in use
+    highlighter: Option<String>,
+    highlighting: bool,
+    // The highlighter's output for the patches shown so far, filled in from the background
+    highlights: RefCell<Highlights>,
+    // gitt.difftool and gitt.difftool-capture, picked over diff.tool
+    difftool: Option<String>,
+    difftool_capture: bool,
+    theme: Theme,
+    // strftime format of the commit dates, RFC 3339 when not set
+    date_format: Option<String>,
+    tree: Option<TreeBrowser>,
+    file_view: Option<FileView>,
+    blame: Option<BlameView>,
+    // A message for the status line, shown until the next key press
+    status: Option<String>,
+    // Commits picked to be printed on exit, only when started with --pick
+    picking: Option<HashSet<Oid>>,
+    picks_confirmed: bool,
+    // A program waiting to be run, like an editor
+    command: Option<ExternalCommand>,
+    external_output: Option<ExternalOutput>,
 }
 
 impl AppModel {
@@ -128,13 +943,44 @@ impl AppModel {
             repository,
             revspec: None,
             filters,
+            local_changes: Vec::new(),
+            local_state: Vec::new(),
+            local_changes_checked: Instant::now(),
+            local_check: LocalChangesCheck::default(),
             revision_index: 0,
             revision_window_index: TableState::default(),
             revision_window_length: 0,
             revision_max: 0,
             diff_index: 0,
+            diff_cursor: None,
+            range_anchor: None,
             diff_window_length: 1,
+            diff_window_width: 80,
             diff_length: 1,
+            file_index: None,
+            diff: RenderedDiff::default(),
+            collapsed: HashMap::new(),
+            collapse_globs: Vec::new(),
+            diff_settings: DiffSettings::default(),
+            marked: None,
+            compare_swapped: false,
+            word_diff: WordDiffMode::Word,
+            syntax: Some(SyntaxHighlighter::new()),
+            highlighter: None,
+            highlighting: false,
+            highlights: RefCell::default(),
+            difftool: None,
+            difftool_capture: false,
+            theme: Theme::default(),
+            date_format: None,
+            tree: None,
+            file_view: None,
+            blame: None,
+            status: None,
+            picking: None,
+            picks_confirmed: false,
+            command: None,
+            external_output: None,
         };
         model.set_revision(revspec)?;
         Ok(model)
@@ -149,200 +995,1780 @@ impl AppModel {
         self.revspec = revision;
         self.revision_index = 0;
         self.revision_window_index.select(Some(0));
-        self.revision_window_length = self.walker().count();
-        self.revision_max = self.walker().count();
+        self.local_check.cancel();
+        self.local_state = self.find_local_changes();
+        self.local_changes = self.local_state.iter().map(|(local, _)| *local).collect();
+        self.local_changes_checked = Instant::now();
+        self.revision_window_length = self.revision_iter().count();
+        self.revision_max = self.revision_iter().count();
         if self.revision_max == 0 {
             return Err(git2::Error::from_str("No commits found"));
         }
-        self.diff_index = 0;
-        self.diff_window_length = 1;
-        self.diff_length = self.diff().len();
+        self.diff_reset();
         Ok(())
     }
 
-    // Returns commits from revision_index to revision_index + revision_window_length
-    pub fn commits(&self) -> Vec<Commit> {
-        self.walker()
+    fn revision_iter(&self) -> impl Iterator<Item = Revision<'_>> {
+        self.local_changes
+            .iter()
+            .map(|local| Revision::Local(*local))
+            .chain(self.walker().map(Revision::Commit))
+    }
+
+    // Returns revisions from revision_index to revision_index + revision_window_length
+    pub fn revisions(&self) -> Vec<Revision<'_>> {
+        self.revision_iter()
             .skip(self.revision_index)
             .take(self.revision_window_length)
             .collect()
     }
 
-    pub fn commit(&self) -> Commit {
-        // TODO: reuse commits?
+    pub fn revision(&self) -> Revision<'_> {
+        // TODO: reuse revisions?
         // TODO: could be empty (or nth goes off the edge of the iterator)
-        self.walker()
+        self.revision_iter()
             .skip(self.revision_index)
             .nth(self.revision_window_index.selected().unwrap_or(0))
             .expect("Unexpected missing commit")
     }
 
-    pub fn diff(&self) -> Vec<Spans> {
-        let commit = self.commit();
-        let mut text = vec![Spans::from(vec![
-            Span::raw(
-                commit
-                    .as_object()
-                    .short_id()
-                    .expect("Unable to write short_id")
-                    .as_str()
-                    .expect("short_id was not valid utf8")
-                    .to_string(),
-            ),
-            Span::raw(" - ".to_string()),
-            Span::raw(commit.id().to_string()),
-        ])];
-        text.append(
-            &mut commit
-                .message()
-                .unwrap_or("INVALID MESSAGE")
-                .split('\n')
-                .map(|s| s.trim_end().to_string())
-                .map(|s| Spans::from(vec![Span::raw(s)]))
-                .collect(),
-        );
-
-        if commit.parents().len() <= 1 {
-            let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
-            let diff = self
-                .repository
-                .diff_tree_to_tree(parent_tree.as_ref(), commit.tree().ok().as_ref(), None)
-                .expect("Unable to create diff");
-
-            let paths: Vec<PathBuf> = self
-                .filters
-                .iter()
-                .flat_map(|filter| {
-                    if let CommitFilter::Path((path, _oids)) = filter {
-                        Some(path.clone())
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+    // Returns the selected commit, or None if one of the local changes rows is selected
+    pub fn commit(&self) -> Option<Commit<'_>> {
+        match self.revision() {
+            Revision::Commit(commit) => Some(commit),
+            Revision::Local(_) => None,
+        }
+    }
 
-            let mut excluded: HashSet<String> = HashSet::new();
+    // Finds which kinds of local changes exist, the rows for them are only shown when the
+    // current branch is being viewed
+    fn find_local_changes(&self) -> LocalState {
+        if self.revspec.is_some() || self.repository.is_bare() {
+            return Vec::new();
+        }
+        local::local_state(&self.repository, &self.diff_settings, &self.filter_paths())
+    }
 
-            diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
-                if !paths.is_empty()
-                    && !(paths.iter().any(|path| {
-                        diff_file_starts_with(&delta.old_file(), path)
-                            || diff_file_starts_with(&delta.new_file(), path)
-                    }))
-                {
-                    delta
-                        .old_file()
-                        .path()
-                        .map(|p| p.to_string_lossy().to_string())
-                        .into_iter()
-                        .for_each(|s| {
-                            let _ = excluded.insert(s);
-                        });
-                    delta
-                        .new_file()
-                        .path()
-                        .map(|p| p.to_string_lossy().to_string())
-                        .into_iter()
-                        .for_each(|s| {
-                            let _ = excluded.insert(s);
-                        });
-                    return true;
-                }
+    // Looks at the working tree and index right away, after a command that may have changed them
+    pub fn refresh_local_changes(&mut self) -> bool {
+        self.local_check.cancel();
+        self.local_changes_checked = Instant::now();
+        let state = self.find_local_changes();
+        self.apply_local_state(state)
+    }
 
-                let (origin, style) = match line.origin() {
-                    'F' => {
-                        text.append(
-                            &mut std::str::from_utf8(line.content())
-                                .unwrap()
-                                .split('\n')
-                                .map(|s| s.trim_end().to_string())
-                                .map(|s| {
-                                    Spans::from(vec![Span::styled(
-                                        s,
-                                        Style::default().fg(Color::Gray),
-                                    )])
-                                })
-                                .collect(),
-                        );
-                        return true;
-                    }
-                    'H' => (None, Style::default().fg(Color::Cyan)),
-                    ' ' => (None, Style::default()),
-                    '+' => (Some(line.origin()), Style::default().fg(Color::Green)),
-                    '-' => (Some(line.origin()), Style::default().fg(Color::Red)),
-                    _ => (None, Style::default()),
-                };
+    // Adds and removes the local changes rows after files were edited, staged or committed
+    // outside of gitt, keeping the same row selected, and rebuilds the diff of a selected row
+    // whose changes were edited. Returns false when nothing changed.
+    fn apply_local_state(&mut self, state: LocalState) -> bool {
+        let hash = |state: &LocalState, local| {
+            state
+                .iter()
+                .find(|(changes, _)| *changes == local)
+                .map(|(_, hash)| *hash)
+        };
+        let edited = match self.revision() {
+            Revision::Local(local) => hash(&self.local_state, local) != hash(&state, local),
+            Revision::Commit(_) => false,
+        };
+        let local_changes: Vec<_> = state.iter().map(|(local, _)| *local).collect();
+        self.local_state = state;
+        if local_changes == self.local_changes {
+            if edited {
+                self.rebuild_diff();
+            }
+            return edited;
+        }
+        let position = self.revision_index + self.revision_window_index.selected().unwrap_or(0);
+        let (position, reset) = match self.revision() {
+            // A row that went away hands the selection to the row now in its place
+            Revision::Local(local) => match local_changes.iter().position(|l| *l == local) {
+                Some(position) => (position, false),
+                None => (0, true),
+            },
+            Revision::Commit(_) => (
+                position - self.local_changes.len() + local_changes.len(),
+                false,
+            ),
+        };
+        self.local_changes = local_changes;
+        self.revision_max = self.revision_iter().count();
+        self.revision_index = std::cmp::min(self.revision_index, position);
+        self.revision_index = std::cmp::max(
+            self.revision_index,
+            (position + 1).saturating_sub(self.revision_window_length),
+        );
+        self.revision_window_index
+            .select(Some(position - self.revision_index));
+        if reset {
+            self.diff_reset();
+        } else if edited {
+            self.rebuild_diff();
+        }
+        true
+    }
 
-                let spans = vec![
-                    Span::styled(origin.unwrap_or(' ').to_string(), style),
-                    Span::styled(
-                        std::str::from_utf8(line.content())
-                            .unwrap()
-                            .trim_end()
-                            .to_string(),
-                        style,
-                    ),
-                ];
-                text.push(Spans::from(spans));
-                true
-            })
-            .expect("Unable to format diff");
+    // Picks up the diff highlighter's output once it's done and looks for local changes every
+    // couple of seconds, returning true when there is something new to draw
+    pub fn tick(&mut self) -> bool {
+        let highlighted = self.highlights.get_mut().poll();
+        if highlighted {
+            self.rebuild_diff();
+        }
+        let changed = match self.local_check.poll() {
+            Some(state) => self.apply_local_state(state),
+            None => false,
+        };
+        if self.local_changes_checked.elapsed() >= LOCAL_CHANGES_INTERVAL
+            && self.revspec.is_none()
+            && !self.repository.is_bare()
+        {
+            self.local_changes_checked = Instant::now();
+            self.local_check
+                .start(&self.repository, &self.diff_settings, self.filter_paths());
+        }
+        highlighted || changed
+    }
 
-            if !excluded.is_empty() {
-                let spans = vec![
-                    Span::styled("".to_string(), Style::default()),
-                    Span::styled("diff hidden:", Style::default().fg(Color::Gray)),
-                ];
+    // Builds the diff of the selection, showing why in the status line when it couldn't be
+    fn load_diff(&mut self) {
+        self.diff = self.build_diff();
+        if let Some(error) = self.diff.error.take() {
+            self.set_status(error);
+        }
+    }
 
-                let mut excluded: Vec<_> = excluded.into_iter().collect();
-                excluded.sort();
+    // Builds the shown diff again without moving away from the lines being looked at
+    fn rebuild_diff(&mut self) {
+        self.load_diff();
+        self.diff_length = self.diff.lines.len();
+        self.diff_index = std::cmp::min(self.diff_index, self.diff_length.saturating_sub(1));
+        self.clamp_cursor();
+    }
 
-                let mut spans: Vec<Spans> = spans
-                    .into_iter()
-                    .chain(
-                        excluded
-                            .into_iter()
-                            .map(|path| Span::styled(path, Style::default().fg(Color::Gray))),
-                    )
-                    .map(|span| Spans::from(vec![span]))
-                    .collect();
-                text.append(&mut spans);
+    fn local_diff(&self, local: LocalChanges) -> Result<git2::Diff<'_>, git2::Error> {
+        let mut git_diff = match local {
+            LocalChanges::Unstaged => self
+                .repository
+                .diff_index_to_workdir(None, Some(&mut self.diff_settings.options()))?,
+            LocalChanges::Staged => {
+                let head = self.repository.head()?.peel_to_tree()?;
+                self.repository.diff_tree_to_index(
+                    Some(&head),
+                    None,
+                    Some(&mut self.diff_settings.options()),
+                )?
             }
+        };
+        if let Some(mut find_options) = self.diff_settings.find_options() {
+            git_diff.find_similar(Some(&mut find_options))?;
         }
-
-        text
+        Ok(git_diff)
     }
 
-    pub fn walker(&self) -> CommitView {
-        CommitView::new(&self.repository, self.revspec.as_ref(), &self.filters)
+    fn build_local_diff(&self, local: LocalChanges) -> RenderedDiff {
+        let mut diff = RenderedDiff::default();
+        diff.push(
+            DiffLineKind::Message,
+            Spans::from(vec![
+                Span::raw(local.description()),
+                Span::styled(
+                    format!("  {}", self.diff_settings),
+                    Style::default().fg(self.theme.muted),
+                ),
+            ]),
+        );
+        diff.push(DiffLineKind::Message, Spans::default());
+        // The working tree or index can change after the row was listed, like HEAD becoming
+        // unborn or the index being locked
+        match self.local_diff(local) {
+            Ok(git_diff) => self.render_patch(&git_diff, &mut diff, &[], false),
+            Err(e) => diff.error = Some(format!("Unable to diff local changes: {}", e.message())),
+        }
+        diff
     }
 
-    pub fn revision_index(&self) -> usize {
-        self.revision_index
+    fn filter_paths(&self) -> Vec<PathBuf> {
+        filter_paths(&self.filters)
     }
 
-    pub fn revision_window(&self) -> (&TableState, usize) {
-        (&self.revision_window_index, self.revision_window_length)
-    }
-    pub fn resize_revision_window(&mut self, length: usize) {
-        assert!(self.revision_window_index.selected().unwrap_or(0) <= length);
-        // TODO: just set the length and then check the count with self.commits().count()
-        let commit_count = self.walker().skip(self.revision_index).take(length).count();
-        // If there are not enough commits to fill the window, shrink it
-        // This can happen if there are very few commits in the repository, or the window was
-        // resized to be larger after scrolling to near the end of the list of commits
-        self.revision_window_length = std::cmp::min(length, commit_count);
+    fn shown_paths(&self, ranges: &[&RangeAt]) -> Vec<PathBuf> {
+        shown_paths(&self.filters, ranges)
     }
 
-    pub fn go_to_first_revision(&mut self) {
-        self.revision_index = 0;
-        self.revision_window_index.select(Some(0));
-        self.diff_reset();
+    fn line_ranges(&self, commit: Oid) -> Vec<&RangeAt> {
+        line_ranges(&self.filters, commit)
     }
 
-    pub fn go_to_last_revision(&mut self) {
-        self.revision_index = self.revision_max - self.revision_window_length;
-        self.revision_window_index
+    fn build_diff(&self) -> RenderedDiff {
+        if let Some(output) = &self.external_output {
+            return self.build_external_output(output);
+        }
+        if let Some(view) = &self.blame {
+            return self.build_blame(view);
+        }
+        if let Some(view) = &self.file_view {
+            return self.build_file_view(view);
+        }
+        let commit = match self.revision() {
+            Revision::Commit(commit) => commit,
+            Revision::Local(local) => return self.build_local_diff(local),
+        };
+        if let Some(marked) = self.marked.filter(|marked| *marked != commit.id()) {
+            let marked = self
+                .repository
+                .find_commit(marked)
+                .expect("Marked commit is missing");
+            return if self.compare_swapped {
+                self.build_comparison(&commit, &marked)
+            } else {
+                self.build_comparison(&marked, &commit)
+            };
+        }
+        self.build_commit_diff(&commit, false)
+    }
+
+    // The message and patch of a commit, as the details pane shows it when nothing is marked
+    fn build_commit_diff(&self, commit: &Commit, wait: bool) -> RenderedDiff {
+        let mut diff = RenderedDiff::default();
+        diff.push(
+            DiffLineKind::Message,
+            Spans::from(vec![
+                Span::raw(short_id(commit)),
+                Span::raw(" - ".to_string()),
+                Span::raw(commit.id().to_string()),
+                Span::styled(
+                    format!("  {}", self.diff_settings),
+                    Style::default().fg(self.theme.muted),
+                ),
+            ]),
+        );
+        commit
+            .message()
+            .unwrap_or("INVALID MESSAGE")
+            .split('\n')
+            .map(|s| s.trim_end().to_string())
+            .for_each(|s| diff.push(DiffLineKind::Message, Spans::from(vec![Span::raw(s)])));
+
+        if let Some(git_diff) = self.commit_diff(commit) {
+            self.render_patch(&git_diff, &mut diff, &self.line_ranges(commit.id()), wait);
+        }
+
+        diff
+    }
+
+    // The lines of a commit's details styled like the details pane draws them at `width`
+    pub fn commit_details(&self, commit: &Commit, width: usize) -> Vec<Spans<'static>> {
+        let diff = self.build_commit_diff(commit, true);
+        (0..diff.lines.len())
+            .map(|index| self.styled_line(&diff, index, width))
+            .collect()
+    }
+
+    fn styled_line(&self, diff: &RenderedDiff, index: usize, width: usize) -> Spans<'static> {
+        let line = &diff.lines[index];
+        match (line.kind, &self.syntax) {
+            (DiffLineKind::Stat(index), _) => stat_line(&diff.stats, index, width, &self.theme),
+            (_, Some(syntax)) if !diff.highlighted && line.file.is_some() => {
+                diff.syntax_line(syntax, index, &self.theme)
+            }
+            _ => line.spans.clone(),
+        }
+    }
+
+    pub fn commit_diff(&self, commit: &Commit) -> Option<git2::Diff<'_>> {
+        commit_diff(&self.repository, &self.diff_settings, commit)
+    }
+
+    fn build_external_output(&self, output: &ExternalOutput) -> RenderedDiff {
+        let mut diff = RenderedDiff::default();
+        diff.push(
+            DiffLineKind::Message,
+            Spans::from(vec![Span::styled(
+                format!("Output of {}", output.name),
+                Style::default().fg(self.theme.muted),
+            )]),
+        );
+        diff.push(DiffLineKind::Message, Spans::default());
+        for line in &output.lines {
+            diff.push(DiffLineKind::Other, line.clone());
+        }
+        diff
+    }
+
+    // Diffs two arbitrary commits, the header spells out which side is which
+    fn build_comparison(&self, old: &Commit, new: &Commit) -> RenderedDiff {
+        let mut diff = RenderedDiff::default();
+        let describe = |commit: &Commit| {
+            format!(
+                "{} {} ({})",
+                short_id(commit),
+                commit.summary().unwrap_or("INVALID MESSAGE"),
+                if Some(commit.id()) == self.marked {
+                    "marked"
+                } else {
+                    "selected"
+                }
+            )
+        };
+        diff.push(
+            DiffLineKind::Message,
+            Spans::from(vec![
+                Span::raw(format!("Comparing {} → {}", short_id(old), short_id(new))),
+                Span::styled(
+                    format!("  {}", self.diff_settings),
+                    Style::default().fg(self.theme.muted),
+                ),
+            ]),
+        );
+        diff.push(
+            DiffLineKind::Message,
+            Spans::from(vec![Span::styled(
+                format!("--- {}", describe(old)),
+                Style::default().fg(self.theme.removed),
+            )]),
+        );
+        diff.push(
+            DiffLineKind::Message,
+            Spans::from(vec![Span::styled(
+                format!("+++ {}", describe(new)),
+                Style::default().fg(self.theme.added),
+            )]),
+        );
+        diff.push(DiffLineKind::Message, Spans::default());
+
+        let git_diff = self.tree_diff(old.tree().ok().as_ref(), new.tree().ok().as_ref());
+        self.render_patch(&git_diff, &mut diff, &[], false);
+        diff
+    }
+
+    // Shows the contents of a file as it was at a commit or its parent, with line numbers. The
+    // lines the commit changed are marked in the gutter.
+    fn build_file_view(&self, view: &FileView) -> RenderedDiff {
+        let mut diff = RenderedDiff::default();
+        let commit = self
+            .repository
+            .find_commit(view.commit)
+            .expect("Viewed commit is missing");
+        let parent = commit.parent(0).ok();
+        let new_tree = commit.tree().ok();
+        let old_tree = parent.as_ref().and_then(|parent| parent.tree().ok());
+
+        // The file may have had another name in the parent
+        let git_diff = self.tree_diff(old_tree.as_ref(), new_tree.as_ref());
+        let (old_path, new_path) = git_diff
+            .deltas()
+            .find(|delta| {
+                delta.new_file().path() == Some(view.path.as_path())
+                    || delta.old_file().path() == Some(view.path.as_path())
+            })
+            .map(|delta| {
+                (
+                    delta.old_file().path().map(|p| p.to_path_buf()),
+                    delta.new_file().path().map(|p| p.to_path_buf()),
+                )
+            })
+            .unwrap_or_else(|| (Some(view.path.clone()), Some(view.path.clone())));
+        let find_blob = |tree: Option<&Tree>, path: Option<&PathBuf>| {
+            let entry = tree?.get_path(path?).ok()?;
+            let blob = self.repository.find_blob(entry.id()).ok()?;
+            Some((entry.filemode(), blob))
+        };
+        let old_blob = find_blob(old_tree.as_ref(), old_path.as_ref());
+        let new_blob = find_blob(new_tree.as_ref(), new_path.as_ref());
+
+        let (side, path, shown) = if view.parent {
+            let side = match &parent {
+                Some(parent) => format!("parent {}", short_id(parent)),
+                None => "parent".to_string(),
+            };
+            (side, old_path.as_ref(), &old_blob)
+        } else {
+            (short_id(&commit), new_path.as_ref(), &new_blob)
+        };
+        let path = path.unwrap_or(&view.path);
+        // Submodules are recorded as the commit they are at, there is no file to show
+        let shown_tree = if view.parent { &old_tree } else { &new_tree };
+        let submodule = shown_tree
+            .as_ref()
+            .and_then(|tree| tree.get_path(path).ok())
+            .filter(|entry| entry.kind() == Some(ObjectType::Commit));
+        if let Some(entry) = submodule {
+            diff.push(
+                DiffLineKind::Message,
+                Spans::from(vec![Span::raw(format!(
+                    "{} is a submodule at commit {} in {}",
+                    path.to_string_lossy(),
+                    entry.id(),
+                    side
+                ))]),
+            );
+            return diff;
+        }
+        let (mode, blob) = match shown {
+            Some(shown) => shown,
+            None => {
+                diff.push(
+                    DiffLineKind::Message,
+                    Spans::from(vec![Span::raw(format!(
+                        "{} does not exist in {}",
+                        path.to_string_lossy(),
+                        side
+                    ))]),
+                );
+                return diff;
+            }
+        };
+        diff.push(
+            DiffLineKind::Message,
+            Spans::from(vec![
+                Span::raw(format!("{} {}", side, path.to_string_lossy())),
+                Span::styled(
+                    format!("  {:06o} {}", mode, tree::format_size(blob.size())),
+                    Style::default().fg(self.theme.muted),
+                ),
+            ]),
+        );
+        diff.push(DiffLineKind::Message, Spans::default());
+        if blob.is_binary() {
+            diff.push(
+                DiffLineKind::Other,
+                Spans::from(vec![Span::styled(
+                    format!("Binary file, {} bytes", blob.size()),
+                    Style::default().fg(self.theme.file_header),
+                )]),
+            );
+            return diff;
+        }
+
+        // Removed lines are numbered in the parent's version, added lines in the commit's
+        let empty: &[u8] = &[];
+        let touched: HashSet<u32> = git2::Patch::from_buffers(
+            old_blob
+                .as_ref()
+                .map(|(_, blob)| blob.content())
+                .unwrap_or(empty),
+            old_path.as_deref(),
+            new_blob
+                .as_ref()
+                .map(|(_, blob)| blob.content())
+                .unwrap_or(empty),
+            new_path.as_deref(),
+            Some(&mut self.diff_settings.options()),
+        )
+        .map(|patch| {
+            let mut touched = HashSet::new();
+            for hunk in 0..patch.num_hunks() {
+                for index in 0..patch.num_lines_in_hunk(hunk).unwrap_or(0) {
+                    if let Ok(line) = patch.line_in_hunk(hunk, index) {
+                        match (line.origin(), view.parent) {
+                            ('-', true) => touched.extend(line.old_lineno()),
+                            ('+', false) => touched.extend(line.new_lineno()),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            touched
+        })
+        .unwrap_or_default();
+        let marker = Style::default().fg(if view.parent {
+            self.theme.removed
+        } else {
+            self.theme.added
+        });
+
+        diff.start_blob(path, blob.id());
+        let content = String::from_utf8_lossy(blob.content());
+        let lines: Vec<&str> = content.lines().collect();
+        let number_width = lines.len().to_string().len();
+        for (index, line) in lines.into_iter().enumerate() {
+            let number = index as u32 + 1;
+            let gutter = if touched.contains(&number) {
+                // Runs of changed lines can be jumped between like hunks
+                if !touched.contains(&(number - 1)) {
+                    diff.hunks.push(diff.lines.len());
+                }
+                diff.count_change(if view.parent { '-' } else { '+' });
+                Span::styled(format!("{:>width$}▌", number, width = number_width), marker)
+            } else {
+                Span::styled(
+                    format!("{:>width$} ", number, width = number_width),
+                    Style::default().fg(self.theme.muted),
+                )
+            };
+            diff.push(
+                DiffLineKind::Context,
+                Spans::from(vec![gutter, Span::raw(line.trim_end().to_string())]),
+            );
+        }
+        diff
+    }
+
+    fn build_blame(&self, view: &BlameView) -> RenderedDiff {
+        let mut diff = RenderedDiff::default();
+        let commit = self
+            .repository
+            .find_commit(view.commit)
+            .expect("Blamed commit is missing");
+        let blob = commit
+            .tree()
+            .and_then(|tree| tree.get_path(&view.path))
+            .and_then(|entry| self.repository.find_blob(entry.id()));
+        diff.push(
+            DiffLineKind::Message,
+            Spans::from(vec![Span::raw(format!(
+                "blame {} {}",
+                short_id(&commit),
+                view.path.to_string_lossy()
+            ))]),
+        );
+        diff.push(DiffLineKind::Message, Spans::default());
+        let mut options = git2::BlameOptions::new();
+        options.newest_commit(view.commit);
+        let (blob, blame) = match (
+            blob,
+            self.repository.blame_file(&view.path, Some(&mut options)),
+        ) {
+            (Ok(blob), _) if blob.is_binary() => {
+                diff.push(
+                    DiffLineKind::Other,
+                    Spans::from(vec![Span::raw("Binary files can't be blamed")]),
+                );
+                return diff;
+            }
+            (Ok(blob), Ok(blame)) => (blob, blame),
+            (Err(e), _) | (_, Err(e)) => {
+                diff.push(
+                    DiffLineKind::Other,
+                    Spans::from(vec![Span::raw(e.message().to_string())]),
+                );
+                return diff;
+            }
+        };
+
+        diff.start_blob(&view.path, blob.id());
+        let content = String::from_utf8_lossy(blob.content());
+        let lines: Vec<&str> = content.lines().collect();
+        let number_width = lines.len().to_string().len();
+        let mut short_ids: HashMap<Oid, String> = HashMap::new();
+        for (index, line) in lines.into_iter().enumerate() {
+            let number = index + 1;
+            let gutter = match blame.get_line(number) {
+                Some(hunk) => {
+                    let id = hunk.final_commit_id();
+                    let short = short_ids.entry(id).or_insert_with(|| {
+                        self.repository
+                            .find_commit(id)
+                            .map(|commit| short_id(&commit))
+                            .unwrap_or_else(|_| id.to_string())
+                    });
+                    let signature = hunk.final_signature();
+                    let author: String = signature.name().unwrap_or("").chars().take(16).collect();
+                    diff.blame.insert(
+                        diff.lines.len(),
+                        BlameOrigin {
+                            commit: id,
+                            path: hunk.path().unwrap_or(&view.path).to_path_buf(),
+                            line: hunk.orig_start_line() + number - hunk.final_start_line(),
+                        },
+                    );
+                    // Lines last changed by the blamed commit itself stand out
+                    Span::styled(
+                        format!(
+                            "{} {:<16} {} {:>width$} ",
+                            short,
+                            author,
+                            format::format_time(
+                                &signature.when(),
+                                Some(self.date_format().unwrap_or(BLAME_DATE_FORMAT)),
+                            ),
+                            number,
+                            width = number_width
+                        ),
+                        Style::default().fg(if id == view.commit {
+                            self.theme.added
+                        } else {
+                            self.theme.blame
+                        }),
+                    )
+                }
+                None => Span::raw(format!("{:>width$} ", number, width = number_width)),
+            };
+            diff.push(
+                DiffLineKind::Context,
+                Spans::from(vec![gutter, Span::raw(line.trim_end().to_string())]),
+            );
+        }
+        diff
+    }
+
+    fn tree_diff(&self, old: Option<&Tree>, new: Option<&Tree>) -> git2::Diff<'_> {
+        tree_diff(&self.repository, &self.diff_settings, old, new)
+    }
+
+    // Appends the diffstat and the patch of a diff, leaving out files hidden by the path filters.
+    // Only the hunks overlapping the given line ranges are shown for the files they are in.
+    // Exports `wait` for the diff highlighter, the interface shows gitt's styling until it's done.
+    fn render_patch(
+        &self,
+        git_diff: &git2::Diff,
+        diff: &mut RenderedDiff,
+        ranges: &[&RangeAt],
+        wait: bool,
+    ) {
+        let paths = self.shown_paths(ranges);
+
+        self.build_diff_stat(git_diff, &paths, diff);
+
+        // Without a working highlighter the patch is styled by gitt
+        let mut highlighted = self
+            .highlight_patch(git_diff, &paths, ranges, wait)
+            .map(Vec::into_iter);
+        diff.highlighted = highlighted.is_some();
+        let mut highlighted_lines = None;
+
+        let mut excluded: HashSet<String> = HashSet::new();
+        let mut changes = ChangeBuffer::default();
+
+        git_diff
+            .print(git2::DiffFormat::Patch, |delta, hunk, line| {
+                if !shows_delta(&paths, &delta) {
+                    delta
+                        .old_file()
+                        .path()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .into_iter()
+                        .for_each(|s| {
+                            let _ = excluded.insert(s);
+                        });
+                    delta
+                        .new_file()
+                        .path()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .into_iter()
+                        .for_each(|s| {
+                            let _ = excluded.insert(s);
+                        });
+                    return true;
+                }
+
+                if let Some(hunk) = &hunk {
+                    if !shows_hunk(ranges, &delta, hunk) {
+                        return true;
+                    }
+                }
+
+                if let Some(files) = &mut highlighted {
+                    if line.origin() == 'F' {
+                        let collapsed = self.delta_collapsed(&delta);
+                        diff.start_file(Some(&delta), collapsed);
+                        let file = files.next();
+                        if collapsed {
+                            diff.push(DiffLineKind::FileHeader, Spans::default());
+                            return true;
+                        }
+                        highlighted_lines = match file {
+                            Some(HighlightedFile::Lines(lines)) => Some(lines.into_iter()),
+                            Some(HighlightedFile::Block(lines)) => {
+                                for spans in lines {
+                                    diff.push(DiffLineKind::Other, spans);
+                                }
+                                None
+                            }
+                            None => None,
+                        };
+                    } else {
+                        diff.count_change(line.origin());
+                        if diff.current_file_collapsed() {
+                            return true;
+                        }
+                    }
+                    if let Some(lines) = &mut highlighted_lines {
+                        if line.origin() == 'H' {
+                            diff.start_hunk(&delta, hunk.as_ref());
+                        }
+                        for (kind, _) in patch_lines(line.origin(), line.content()) {
+                            diff.push(kind, lines.next().unwrap_or_default());
+                        }
+                    }
+                    return true;
+                }
+
+                if line.origin() != 'F' && diff.current_file_collapsed() {
+                    diff.count_change(line.origin());
+                    return true;
+                }
+
+                if changes.ends_run(line.origin()) {
+                    diff.append(changes.flush(self.word_diff, &self.theme));
+                }
+
+                let (kind, style) = match line.origin() {
+                    'F' => {
+                        let collapsed = self.delta_collapsed(&delta);
+                        diff.start_file(Some(&delta), collapsed);
+                        if collapsed {
+                            diff.push(DiffLineKind::FileHeader, Spans::default());
+                            return true;
+                        }
+                        file_header(&delta, &String::from_utf8_lossy(line.content()))
+                            .into_iter()
+                            .for_each(|s| {
+                                diff.push(
+                                    DiffLineKind::FileHeader,
+                                    Spans::from(vec![Span::styled(
+                                        s,
+                                        Style::default().fg(self.theme.file_header),
+                                    )]),
+                                )
+                            });
+                        return true;
+                    }
+                    'H' => {
+                        diff.start_hunk(&delta, hunk.as_ref());
+                        (
+                            DiffLineKind::HunkHeader,
+                            Style::default().fg(self.theme.hunk_header),
+                        )
+                    }
+                    ' ' => (DiffLineKind::Context, Style::default()),
+                    // Only the line ending is trimmed so changes to trailing whitespace still show
+                    '+' | '-' => {
+                        diff.count_change(line.origin());
+                        changes.push(
+                            line.origin(),
+                            String::from_utf8_lossy(line.content())
+                                .trim_end_matches(&['\n', '\r'][..]),
+                        );
+                        return true;
+                    }
+                    _ => (DiffLineKind::Other, Style::default()),
+                };
+
+                let spans = vec![
+                    Span::styled(" ".to_string(), style),
+                    Span::styled(
+                        String::from_utf8_lossy(line.content())
+                            .trim_end_matches(&['\n', '\r'][..])
+                            .to_string(),
+                        style,
+                    ),
+                ];
+                diff.push(kind, Spans::from(spans));
+                true
+            })
+            .expect("Unable to format diff");
+        diff.append(changes.flush(self.word_diff, &self.theme));
+        diff.start_file(None, false);
+        diff.summarise_collapsed(&self.theme);
+
+        if !excluded.is_empty() {
+            let spans = vec![
+                Span::styled("".to_string(), Style::default()),
+                Span::styled("diff hidden:", Style::default().fg(self.theme.file_header)),
+            ];
+
+            let mut excluded: Vec<_> = excluded.into_iter().collect();
+            excluded.sort();
+
+            spans
+                .into_iter()
+                .chain(
+                    excluded.into_iter().map(|path| {
+                        Span::styled(path, Style::default().fg(self.theme.file_header))
+                    }),
+                )
+                .for_each(|span| diff.push(DiffLineKind::Other, Spans::from(vec![span])));
+        }
+    }
+
+    // Adds a git show --stat style summary of the files in the diff
+    fn build_diff_stat(&self, git_diff: &git2::Diff, paths: &[PathBuf], diff: &mut RenderedDiff) {
+        for index in 0..git_diff.deltas().len() {
+            let delta = git_diff.get_delta(index).expect("delta index out of range");
+            if !shows_delta(paths, &delta) {
+                continue;
+            }
+            diff.stats.push(diff_stat_entry(git_diff, index));
+            diff.push(DiffLineKind::Stat(diff.stats.len() - 1), Spans::default());
+        }
+        if diff.stats.is_empty() {
+            return;
+        }
+
+        // Totals come from libgit2 unless a path filter hides some of the files
+        let (files_changed, insertions, deletions) = if paths.is_empty() {
+            let stats = git_diff.stats().expect("Unable to get diff stats");
+            (stats.files_changed(), stats.insertions(), stats.deletions())
+        } else {
+            diff.stats
+                .iter()
+                .fold((0, 0, 0), |(files, insertions, deletions), stat| {
+                    (
+                        files + 1,
+                        insertions + stat.additions,
+                        deletions + stat.deletions,
+                    )
+                })
+        };
+        let plural = |count: usize, singular: &str, plural: &str| {
+            format!("{} {}", count, if count == 1 { singular } else { plural })
+        };
+        let mut totals = format!(" {} changed", plural(files_changed, "file", "files"));
+        if insertions > 0 || deletions == 0 {
+            totals.push_str(&format!(
+                ", {}(+)",
+                plural(insertions, "insertion", "insertions")
+            ));
+        }
+        if deletions > 0 || insertions == 0 {
+            totals.push_str(&format!(
+                ", {}(-)",
+                plural(deletions, "deletion", "deletions")
+            ));
+        }
+        diff.push(DiffLineKind::Other, Spans::from(vec![Span::raw(totals)]));
+        diff.push(DiffLineKind::Other, Spans::default());
+    }
+
+    // Returns the lines of the diff that fit in the window. Syntax highlighting only goes as far as
+    // the last visible line.
+    pub fn diff_window(&self) -> Vec<Spans<'_>> {
+        let end = std::cmp::min(
+            self.diff_index + self.diff_window_length,
+            self.diff.lines.len(),
+        );
+        let start = std::cmp::min(self.diff_index, end);
+        (start..end)
+            .map(|index| {
+                let spans = self.styled_line(&self.diff, index, self.diff_window_width);
+                let selected = match (self.diff_cursor, self.range_anchor) {
+                    (Some(cursor), Some(anchor)) => {
+                        (cursor.min(anchor)..=cursor.max(anchor)).contains(&index)
+                    }
+                    (cursor, _) => cursor == Some(index),
+                };
+                if selected {
+                    Spans::from(
+                        spans
+                            .0
+                            .into_iter()
+                            .map(|span| {
+                                Span::styled(span.content, span.style.bg(self.theme.cursor))
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                } else {
+                    spans
+                }
+            })
+            .collect()
+    }
+
+    // Pipes the patch through the diff highlighter, returning the output for each shown file in
+    // order. The interface gets None while the highlighter runs in the background and the diff is
+    // rebuilt when it finishes, exports wait for it.
+    fn highlight_patch(
+        &self,
+        git_diff: &git2::Diff,
+        paths: &[PathBuf],
+        ranges: &[&RangeAt],
+        wait: bool,
+    ) -> Option<Vec<HighlightedFile>> {
+        let command = self.highlighter.as_ref().filter(|_| self.highlighting)?;
+        let mut files: Vec<Vec<String>> = Vec::new();
+        git_diff
+            .print(git2::DiffFormat::Patch, |delta, hunk, line| {
+                if !shows_delta(paths, &delta) {
+                    return true;
+                }
+                if let Some(hunk) = &hunk {
+                    if !shows_hunk(ranges, &delta, hunk) {
+                        return true;
+                    }
+                }
+                if line.origin() == 'F' {
+                    files.push(Vec::new());
+                }
+                // Collapsed files aren't shown so they aren't highlighted
+                if let Some(file) = files.last_mut().filter(|_| !self.delta_collapsed(&delta)) {
+                    file.extend(
+                        patch_lines(line.origin(), line.content())
+                            .into_iter()
+                            .map(|(kind, text)| git_colored(kind, &text)),
+                    );
+                }
+                true
+            })
+            .ok()?;
+        if files.iter().all(Vec::is_empty) {
+            return None;
+        }
+        let patch = Patch {
+            command: command.clone(),
+            columns: self.diff_window_width,
+            files,
+        };
+        let mut highlights = self.highlights.borrow_mut();
+        if wait {
+            highlights.get_or_run(patch)
+        } else {
+            highlights.get_or_start(patch)
+        }
+    }
+
+    fn delta_collapsed(&self, delta: &DiffDelta) -> bool {
+        delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map(|path| self.is_collapsed(path))
+            .unwrap_or(false)
+    }
+
+    fn is_collapsed(&self, path: &Path) -> bool {
+        self.collapsed.get(path).copied().unwrap_or_else(|| {
+            self.collapse_globs
+                .iter()
+                .any(|pattern| glob::matches(pattern, path))
+        })
+    }
+
+    pub fn set_collapse_globs(&mut self, globs: Vec<String>) {
+        self.collapse_globs = globs;
+        self.diff_reset();
+    }
+
+    // Collapses or expands the selected file and keeps its header at the top of the window
+    pub fn toggle_file_collapsed(&mut self) {
+        let index = match self.file_index {
+            Some(index) => index,
+            None => return,
+        };
+        let file = &self.diff.files[index];
+        self.collapsed.insert(file.path.clone(), !file.collapsed);
+
+        self.load_diff();
+        self.diff_length = self.diff.lines.len();
+        self.select_file(index);
+    }
+
+    pub fn marked(&self) -> Option<Oid> {
+        self.marked
+    }
+
+    // Marks the selected commit for comparison, or clears the mark if it is already marked
+    pub fn toggle_mark(&mut self) {
+        // Local changes can't be marked since they aren't commits
+        let selected = match self.commit() {
+            Some(commit) => commit.id(),
+            None => return,
+        };
+        self.marked = if self.marked == Some(selected) {
+            None
+        } else {
+            Some(selected)
+        };
+        self.compare_swapped = false;
+        self.diff_reset();
+    }
+
+    pub fn swap_compared(&mut self) {
+        if self.marked.is_some() {
+            self.compare_swapped = !self.compare_swapped;
+            self.diff_reset();
+        }
+    }
+
+    // Writes the selected commit as a patch file in the working tree, or every commit from
+    // the marked commit to the selected one when a commit is marked
+    pub fn export_patches(&mut self) {
+        let status = match self.write_patches() {
+            Ok(paths) if paths.len() == 1 => format!("Wrote {}", paths[0].display()),
+            Ok(paths) => format!(
+                "Wrote {} patches, {} to {}",
+                paths.len(),
+                paths[0].display(),
+                paths[paths.len() - 1].display()
+            ),
+            Err(error) => format!("Unable to export patches: {}", error),
+        };
+        self.set_status(status);
+    }
+
+    fn write_patches(&self) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let selected = self.commit().ok_or("Local changes aren't commits")?;
+        let marked = match self.marked {
+            Some(marked) => self.repository.find_commit(marked)?,
+            None => selected.clone(),
+        };
+        let (old, new) = if self
+            .repository
+            .graph_descendant_of(marked.id(), selected.id())?
+        {
+            (selected, marked)
+        } else if marked.id() == selected.id()
+            || self
+                .repository
+                .graph_descendant_of(selected.id(), marked.id())?
+        {
+            (marked, selected)
+        } else {
+            return Err("the marked and selected commits are on different branches".into());
+        };
+        let commits = mbox::inclusive_range(&self.repository, &old, &new)?;
+        if commits.is_empty() {
+            return Err("merges can't be exported".into());
+        }
+        let patches = mbox::format_patches(&self.repository, &commits)?;
+        // Bare repositories have no working tree, the patches go in the current directory
+        let directory = self.repository.workdir().unwrap_or_else(|| Path::new("."));
+        Ok(mbox::write_patches(directory, &commits, &patches)?)
+    }
+
+    pub fn diff_settings(&self) -> &DiffSettings {
+        &self.diff_settings
+    }
+
+    pub fn set_diff_settings(&mut self, settings: DiffSettings) {
+        self.diff_settings = settings;
+        self.diff_reset();
+    }
+
+    pub fn word_diff(&self) -> WordDiffMode {
+        self.word_diff
+    }
+
+    pub fn set_word_diff(&mut self, mode: WordDiffMode) {
+        self.word_diff = mode;
+        self.diff_reset();
+    }
+
+    pub fn syntax_highlighting(&self) -> bool {
+        self.syntax.is_some()
+    }
+
+    pub fn set_syntax_highlighting(&mut self, enabled: bool) {
+        // Loading the syntax definitions is slow enough that they are dropped only when disabled
+        if enabled && self.syntax.is_none() {
+            self.syntax = Some(SyntaxHighlighter::new());
+        } else if !enabled {
+            self.syntax = None;
+        }
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.diff_reset();
+    }
+
+    pub fn date_format(&self) -> Option<&str> {
+        self.date_format.as_deref()
+    }
+
+    pub fn set_date_format(&mut self, format: Option<String>) {
+        self.date_format = format;
+    }
+
+    pub fn set_difftool(&mut self, tool: Option<String>, capture: bool) {
+        self.difftool = tool;
+        self.difftool_capture = capture;
+    }
+
+    pub fn set_highlighter(&mut self, command: Option<String>, enabled: bool) {
+        self.highlighting = enabled && command.is_some();
+        self.highlighter = command;
+        self.diff_reset();
+    }
+
+    pub fn toggle_highlighting(&mut self) {
+        if self.highlighter.is_none() {
+            self.set_status(
+                "No diff highlighter configured, set gitt.highlighter or interactive.diffFilter"
+                    .to_string(),
+            );
+            return;
+        }
+        self.highlighting = !self.highlighting;
+        self.diff_reset();
+    }
+
+    pub fn tree(&self) -> Option<&TreeBrowser> {
+        self.tree.as_ref()
+    }
+
+    // Browses the tree of the selected commit, local changes have no tree to browse
+    pub fn open_tree(&mut self) {
+        let commit = match self.commit() {
+            Some(commit) => commit.id(),
+            None => return,
+        };
+        if self.tree.is_none() {
+            match TreeBrowser::new(&self.repository, commit) {
+                Ok(tree) => self.tree = Some(tree),
+                Err(e) => return self.set_status(tree_error(&e)),
+            }
+        }
+        self.app_state = AppState::Tree;
+    }
+
+    pub fn close_tree(&mut self) {
+        self.tree = None;
+        self.app_state = AppState::Commits;
+        self.diff_reset();
+    }
+
+    pub fn increment_tree_row(&mut self) {
+        if let Some(tree) = &mut self.tree {
+            tree.increment();
+        }
+    }
+
+    pub fn decrement_tree_row(&mut self) {
+        if let Some(tree) = &mut self.tree {
+            tree.decrement();
+        }
+    }
+
+    pub fn go_to_first_tree_row(&mut self) {
+        if let Some(tree) = &mut self.tree {
+            tree.selected = 0;
+        }
+    }
+
+    pub fn go_to_last_tree_row(&mut self) {
+        if let Some(tree) = &mut self.tree {
+            tree.selected = tree.rows.len().saturating_sub(1);
+        }
+    }
+
+    // Expands the selected directory, or shows the selected file in the details pane
+    pub fn open_tree_row(&mut self) {
+        let tree = match &mut self.tree {
+            Some(tree) => tree,
+            None => return,
+        };
+        let (path, is_directory, expanded) = match tree.selected_row() {
+            Some(row) if row.is_submodule() => {
+                let status = format!(
+                    "{} is a submodule at commit {}",
+                    row.path.to_string_lossy(),
+                    row.id
+                );
+                self.set_status(status);
+                return;
+            }
+            Some(row) => (row.path.clone(), row.is_directory(), row.expanded),
+            None => return,
+        };
+        if is_directory {
+            if let Err(e) = tree.set_expanded(&self.repository, !expanded) {
+                self.set_status(tree_error(&e));
+            }
+        } else {
+            let commit = tree.commit;
+            self.file_view = Some(FileView {
+                commit,
+                path,
+                parent: false,
+            });
+            self.app_state = AppState::Details;
+            self.diff_reset();
+        }
+    }
+
+    pub fn collapse_tree_row(&mut self) {
+        if let Some(tree) = &mut self.tree {
+            if let Err(e) = tree.collapse_or_parent(&self.repository) {
+                self.set_status(tree_error(&e));
+            }
+        }
+    }
+
+    pub fn file_view(&self) -> Option<&FileView> {
+        self.file_view.as_ref()
+    }
+
+    // Shows the whole of the selected file at the selected commit in place of the diff
+    pub fn open_file_view(&mut self) {
+        let commit = match self.commit() {
+            Some(commit) => commit.id(),
+            None => return,
+        };
+        // Above the first file the first file is used
+        let path = match self.diff.files.get(self.file_index.unwrap_or(0)) {
+            Some(file) => file.path.clone(),
+            None => return,
+        };
+        self.file_view = Some(FileView {
+            commit,
+            path,
+            parent: false,
+        });
+        self.app_state = AppState::Details;
+        self.diff_reset();
+    }
+
+    pub fn external_output(&self) -> Option<&ExternalOutput> {
+        self.external_output.as_ref()
+    }
+
+    pub fn close_external_output(&mut self) {
+        if self.external_output.take().is_some() {
+            self.diff_reset();
+        }
+    }
+
+    pub fn close_file_view(&mut self) {
+        if self.file_view.take().is_some() {
+            self.diff_reset();
+        }
+    }
+
+    // Switches between the commit's version of the viewed file and its parent's version
+    pub fn toggle_file_view_parent(&mut self) {
+        if let Some(view) = &mut self.file_view {
+            view.parent = !view.parent;
+            self.diff_reset();
+        }
+    }
+
+    pub fn blame(&self) -> Option<&BlameView> {
+        self.blame.as_ref()
+    }
+
+    // Blames the viewed file, or the selected file of the diff
+    pub fn open_blame(&mut self) {
+        let commit = match self.commit() {
+            Some(commit) => commit.id(),
+            None => return,
+        };
+        let path = match &self.file_view {
+            Some(view) => view.path.clone(),
+            None => match self.diff.files.get(self.file_index.unwrap_or(0)) {
+                Some(file) => file.path.clone(),
+                None => return,
+            },
+        };
+        self.blame = Some(BlameView {
+            selected: commit,
+            commit,
+            path,
+            line: 1,
+        });
+        self.app_state = AppState::Details;
+        self.diff_reset();
+    }
+
+    pub fn close_blame(&mut self) {
+        if self.blame.take().is_some() {
+            self.diff_reset();
+        }
+    }
+
+    fn blame_origin(&self) -> Option<&BlameOrigin> {
+        self.diff_cursor.and_then(|line| self.diff.blame.get(&line))
+    }
+
+    // Selects the commit that last changed the line under the cursor and shows its diff
+    pub fn select_blamed_commit(&mut self) {
+        let (commit, path) = match self.blame_origin() {
+            Some(origin) => (origin.commit, origin.path.clone()),
+            None => return,
+        };
+        if self.select_commit(commit) {
+            if let Some(index) = self.diff.files.iter().position(|file| file.path == path) {
+                self.select_file(index);
+            }
+        }
+    }
+
+    // Blames the file again as of the parent of the commit that last changed the line under the
+    // cursor, following the file if that commit renamed it
+    pub fn blame_parent(&mut self) {
+        let (commit, path, line) = match self.blame_origin() {
+            Some(origin) => (origin.commit, origin.path.clone(), origin.line),
+            None => return,
+        };
+        let (parent, path) = {
+            let commit = self
+                .repository
+                .find_commit(commit)
+                .expect("Blamed commit is missing");
+            let parent = match commit.parent(0) {
+                Ok(parent) => parent,
+                Err(_) => return,
+            };
+            let git_diff = self.tree_diff(parent.tree().ok().as_ref(), commit.tree().ok().as_ref());
+            let path = git_diff
+                .deltas()
+                .find(|delta| delta.new_file().path() == Some(path.as_path()))
+                .and_then(|delta| delta.old_file().path().map(|p| p.to_path_buf()))
+                .unwrap_or(path);
+            (parent.id(), path)
+        };
+        if let Some(view) = &mut self.blame {
+            view.commit = parent;
+            view.path = path;
+            view.line = line;
+        }
+        self.diff_reset();
+    }
+
+    // Moves the selection to a commit, returning false if it isn't in the list
+    fn select_commit(&mut self, id: Oid) -> bool {
+        let position = match self.revision_iter().position(|revision| match revision {
+            Revision::Commit(commit) => commit.id() == id,
+            Revision::Local(_) => false,
+        }) {
+            Some(position) => position,
+            None => return false,
+        };
+        self.revision_index = std::cmp::min(
+            position,
+            self.revision_max
+                .saturating_sub(self.revision_window_length),
+        );
+        self.revision_window_index
+            .select(Some(position - self.revision_index));
+        self.diff_reset();
+        true
+    }
+
+    pub fn add_line_range_filter(&mut self, history: LineHistory) {
+        self.filters.push(CommitFilter::Lines(history));
+        if self.set_revision(self.revspec.clone()).is_err() {
+            // Nothing changed the lines in the commits being viewed
+            let path = match self.filters.pop() {
+                Some(CommitFilter::Lines(history)) => history.spec.path,
+                _ => PathBuf::new(),
+            };
+            self.set_revision(self.revspec.clone())
+                .expect("Unable to restore the commit list");
+            self.set_status(format!(
+                "No commits in the list change these lines of {}",
+                path.to_string_lossy()
+            ));
+        }
+    }
+
+    pub fn clear_line_range_filters(&mut self) {
+        let count = self.filters.len();
+        self.filters
+            .retain(|filter| !matches!(filter, CommitFilter::Lines(_)));
+        if self.filters.len() == count {
+            return;
+        }
+        self.set_revision(self.revspec.clone())
+            .expect("Unable to restore the commit list");
+    }
+
+    pub fn line_range_marked(&self) -> bool {
+        self.range_anchor.is_some()
+    }
+
+    pub fn clear_line_range_mark(&mut self) {
+        self.range_anchor = None;
+    }
+
+    // The first press marks one end of a range of lines in the viewed file, the second follows
+    // the history of the lines between the mark and the cursor
+    pub fn mark_line_range(&mut self) {
+        let (view, cursor) = match (&self.file_view, self.diff_cursor) {
+            (Some(view), Some(cursor)) => (view, cursor),
+            _ => return,
+        };
+        let anchor = match self.range_anchor {
+            Some(anchor) => anchor,
+            None => {
+                self.range_anchor = Some(cursor);
+                return;
+            }
+        };
+        let file = match self.diff.files.first() {
+            Some(file) => file,
+            None => return,
+        };
+        let first = std::cmp::max(std::cmp::min(anchor, cursor), file.line);
+        let last = std::cmp::max(anchor, cursor);
+        if last < first {
+            return;
+        }
+        let spec = LineRangeSpec {
+            path: file.path.clone(),
+            lines: Lines::Numbers {
+                start: first - file.line + 1,
+                end: last - file.line + 1,
+            },
+        };
+        let start = if view.parent {
+            let commit = self
+                .repository
+                .find_commit(view.commit)
+                .expect("Viewed commit is missing");
+            match commit.parent_id(0) {
+                Ok(parent) => parent,
+                Err(_) => return,
+            }
+        } else {
+            view.commit
+        };
+        match LineHistory::new(&self.repository, start, spec) {
+            Ok(history) => self.add_line_range_filter(history),
+            Err(e) => {
+                self.range_anchor = None;
+                self.set_status(format!("Unable to follow the lines: {}", e.message()));
+            }
+        }
+    }
+
+    // Opens the line at the cursor, or the top of the details pane, in an editor. The file in the
+    // working tree is opened when there is one, otherwise the file as of the diff is written to a
+    // temporary file.
+    pub fn edit_line(&mut self) {
+        let index = self.diff_cursor.unwrap_or(self.diff_index);
+        let file_line = match self.diff.file_line(index) {
+            Some(file_line) => file_line,
+            None => {
+                self.set_status("No file at this line to edit".to_string());
+                return;
+            }
+        };
+        let working_file = self
+            .repository
+            .workdir()
+            .map(|workdir| workdir.join(&file_line.path))
+            .filter(|path| path.is_file());
+        let command = match (working_file, file_line.blob) {
+            (Some(path), _) => ExternalCommand::editor(path, file_line.line, Vec::new()),
+            (None, Some(blob)) => match self.write_temporary_blob(&file_line.path, blob) {
+                Ok(path) => ExternalCommand::editor(path.clone(), file_line.line, vec![path]),
+                Err(error) => {
+                    self.set_status(format!("Unable to write a temporary file: {}", error));
+                    return;
+                }
+            },
+            (None, None) => {
+                self.set_status(format!("{} does not exist", file_line.path.display()));
+                return;
+            }
+        };
+        self.command = Some(command);
+    }
+
+    // Keeps the file name so editors still recognise the kind of file
+    fn write_temporary_blob(
+        &self,
+        path: &Path,
+        blob: Oid,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let blob = self.repository.find_blob(blob)?;
+        let name = path.file_name().ok_or("path has no file name")?;
+        let directory = external::temporary_directory()?;
+        let temporary = directory.join(format!(
+            "{}-{}",
+            &blob.id().to_string()[..7],
+            name.to_string_lossy()
+        ));
+        if let Err(error) = std::fs::write(&temporary, blob.content()) {
+            external::remove_temporary(&temporary);
+            return Err(error.into());
+        }
+        Ok(temporary)
+    }
+
+    // Runs the configured diff program on the files of the selected commit, or only on the
+    // selected file when the file list or the details pane has one
+    pub fn run_diff_tool(&mut self) {
+        let tool = match self
+            .repository
+            .config()
+            .map_err(|e| e.message().to_string())
+            .and_then(|config| {
+                DiffTool::from_config(&config, self.difftool.clone(), self.difftool_capture)
+            }) {
+            Ok(tool) => tool,
+            Err(error) => {
+                self.set_status(error);
+                return;
+            }
+        };
+        let selected = match self.app_state {
+            AppState::Commits => None,
+            _ => match (&self.blame, &self.file_view, self.file_index) {
+                (Some(view), _, _) => Some(view.path.clone()),
+                (None, Some(view), _) => Some(view.path.clone()),
+                (None, None, Some(index)) => self.diff.files.get(index).map(|f| f.path.clone()),
+                (None, None, None) => None,
+            },
+        };
+        let mut cleanup = Vec::new();
+        match self.diff_tool_files(selected.as_deref(), &mut cleanup) {
+            Ok(files) if files.is_empty() => {
+                self.set_status("No changed files to diff".to_string());
+            }
+            Ok(files) => self.command = Some(tool.command(files, cleanup)),
+            Err(error) => {
+                for path in &cleanup {
+                    external::remove_temporary(path);
+                }
+                self.set_status(format!("Unable to diff: {}", error));
+            }
+        }
+    }
+
+    // Writes both sides of each changed file to temporary files for a diff program. Files in the
+    // working tree are used as they are.
+    fn diff_tool_files(
+        &self,
+        selected: Option<&Path>,
+        cleanup: &mut Vec<PathBuf>,
+    ) -> Result<Vec<DiffFiles>, Box<dyn std::error::Error>> {
+        let (git_diff, paths, workdir_changes) = match self.revision() {
+            Revision::Local(local) => (
+                self.local_diff(local)?,
+                self.filter_paths(),
+                local == LocalChanges::Unstaged,
+            ),
+            Revision::Commit(commit) => {
+                let git_diff = match self.marked.filter(|marked| *marked != commit.id()) {
+                    Some(marked) => {
+                        let marked = self.repository.find_commit(marked)?;
+                        let (old, new) = if self.compare_swapped {
+                            (&commit, &marked)
+                        } else {
+                            (&marked, &commit)
+                        };
+                        self.tree_diff(old.tree().ok().as_ref(), new.tree().ok().as_ref())
+                    }
+                    None => self.commit_diff(&commit).ok_or("merges are not diffed")?,
+                };
+                let paths = self.shown_paths(&self.line_ranges(commit.id()));
+                (git_diff, paths, false)
+            }
+        };
+        let mut files = Vec::new();
+        for delta in git_diff.deltas() {
+            let path = match delta.new_file().path().or_else(|| delta.old_file().path()) {
+                Some(path) => path.to_path_buf(),
+                None => continue,
+            };
+            if delta.status() == Delta::Unmodified || !shows_delta(&paths, &delta) {
+                continue;
+            }
+            if let Some(selected) = selected {
+                if delta.new_file().path() != Some(selected)
+                    && delta.old_file().path() != Some(selected)
+                {
+                    continue;
+                }
+            }
+            let (old_file, old_mode) = self.diff_tool_side(&delta.old_file(), false, cleanup)?;
+            let (new_file, new_mode) =
+                self.diff_tool_side(&delta.new_file(), workdir_changes, cleanup)?;
+            files.push(DiffFiles {
+                path,
+                old_file,
+                old_id: delta.old_file().id().to_string(),
+                old_mode,
+                new_file,
+                new_id: delta.new_file().id().to_string(),
+                new_mode,
+            });
+        }
+        Ok(files)
+    }
+
+    // The file and mode given to a diff program for one side of a change
+    fn diff_tool_side(
+        &self,
+        file: &DiffFile,
+        in_workdir: bool,
+        cleanup: &mut Vec<PathBuf>,
+    ) -> Result<(PathBuf, String), Box<dyn std::error::Error>> {
+        let path = match file.path() {
+            Some(path) if file.exists() => path,
+            _ => return Ok((PathBuf::from("/dev/null"), ".".to_string())),
+        };
+        let mode = format!("{:06o}", u32::from(file.mode()));
+        match self.repository.workdir() {
+            Some(workdir) if in_workdir => Ok((workdir.join(path), mode)),
+            _ => {
+                let temporary = self.write_temporary_blob(path, file.id())?;
+                cleanup.push(temporary.clone());
+                Ok((temporary, mode))
+            }
+        }
+    }
+
+    pub fn take_command(&mut self) -> Option<ExternalCommand> {
+        self.command.take()
+    }
+
+    // Shows what went wrong with a program or what it printed, and picks up any changes it made
+    // to the files while staying at the same place in the details pane
+    pub fn command_finished(&mut self, name: &str, result: Result<Option<String>, String>) {
+        match result {
+            Ok(Some(output)) => {
+                self.external_output = Some(ExternalOutput {
+                    commit: self.commit().map(|commit| commit.id()),
+                    name: name.to_string(),
+                    lines: ansi::parse(&output),
+                });
+                self.app_state = AppState::Details;
+                self.diff_reset();
+                return;
+            }
+            Ok(None) => {}
+            Err(error) => self.set_status(error),
+        }
+        self.refresh_local_changes();
+        self.rebuild_diff();
+    }
+
+    pub fn start_picking(&mut self) {
+        self.picking = Some(HashSet::new());
+    }
+
+    pub fn is_picking(&self) -> bool {
+        self.picking.is_some()
+    }
+
+    pub fn pick_count(&self) -> Option<usize> {
+        self.picking.as_ref().map(|picked| picked.len())
+    }
+
+    pub fn is_picked(&self, id: Oid) -> bool {
+        self.picking
+            .as_ref()
+            .map(|picked| picked.contains(&id))
+            .unwrap_or(false)
+    }
+
+    pub fn toggle_pick(&mut self) {
+        let id = match self.commit() {
+            Some(commit) => commit.id(),
+            None => return,
+        };
+        if let Some(picked) = &mut self.picking {
+            if !picked.remove(&id) {
+                picked.insert(id);
+            }
+        }
+    }
+
+    // Finishes with the picked commits, or the selected commit if none were picked
+    pub fn finish_picking(&mut self) {
+        let selected = self.commit().map(|commit| commit.id());
+        if let Some(picked) = &mut self.picking {
+            if picked.is_empty() {
+                picked.extend(selected);
+            }
+            self.picks_confirmed = true;
+            self.app_state = AppState::Finished;
+        }
+    }
+
+    // The picked commits in the order they are listed, nothing is returned if picking was
+    // cancelled
+    pub fn picked(&self) -> Vec<Commit<'_>> {
+        match &self.picking {
+            Some(picked) if self.picks_confirmed => self
+                .walker()
+                .filter(|commit| picked.contains(&commit.id()))
+                .take(picked.len())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = Some(status);
+    }
+
+    pub fn clear_status(&mut self) {
+        self.status = None;
+    }
+
+    // The path of the file selected in the tree browser, shown in full or at the top of the diff
+    fn selected_path(&self) -> Option<PathBuf> {
+        if self.app_state == AppState::Tree {
+            if let Some(row) = self.tree.as_ref().and_then(|tree| tree.selected_row()) {
+                return Some(row.path.clone());
+            }
+        }
+        // Above the first file the first file is used, like when opening the file view
+        let index = match (&self.file_view, &self.blame) {
+            (None, None) => self.file_index.unwrap_or(0),
+            _ => 0,
+        };
+        self.diff.files.get(index).map(|file| file.path.clone())
+    }
+
+    pub fn yank_text(&self, yank: Yank) -> Option<String> {
+        if yank == Yank::Path {
+            return self
+                .selected_path()
+                .map(|path| path.to_string_lossy().to_string());
+        }
+        let commit = self.commit()?;
+        let subject = commit.summary().unwrap_or("INVALID MESSAGE").to_string();
+        Some(match yank {
+            Yank::Id => commit.id().to_string(),
+            Yank::ShortId => short_id(&commit),
+            Yank::Subject => subject,
+            Yank::Reference => format!("{} (\"{}\")", short_id(&commit), subject),
+            Yank::Path => unreachable!(),
+        })
+    }
+
+    pub fn repository(&self) -> &Repository {
+        &self.repository
+    }
+
+    pub fn walker(&self) -> CommitView<'_> {
+        CommitView::new(&self.repository, self.revspec.as_ref(), &self.filters)
+    }
+
+    pub fn revision_index(&self) -> usize {
+        self.revision_index
+    }
+
+    pub fn revision_window(&self) -> (&TableState, usize) {
+        (&self.revision_window_index, self.revision_window_length)
+    }
+    pub fn resize_revision_window(&mut self, length: usize) {
+        assert!(self.revision_window_index.selected().unwrap_or(0) <= length);
+        // TODO: just set the length and then check the count with self.commits().count()
+        let commit_count = self
+            .revision_iter()
+            .skip(self.revision_index)
+            .take(length)
+            .count();
+        // If there are not enough commits to fill the window, shrink it
+        // This can happen if there are very few commits in the repository, or the window was
+        // resized to be larger after scrolling to near the end of the list of commits
+        self.revision_window_length = std::cmp::min(length, commit_count);
+    }
+
+    pub fn go_to_first_revision(&mut self) {
+        self.revision_index = 0;
+        self.revision_window_index.select(Some(0));
+        self.diff_reset();
+    }
+
+    pub fn go_to_last_revision(&mut self) {
+        self.revision_index = self.revision_max - self.revision_window_length;
+        self.revision_window_index
             .select(Some(self.revision_window_length - 1));
         self.diff_reset();
     }
@@ -373,18 +2799,89 @@ impl AppModel {
     }
 
     fn diff_reset(&mut self) {
+        // Viewed files and program output belong to the commit they were opened from
+        if self.external_output.as_ref().map(|output| output.commit)
+            != Some(self.commit().map(|c| c.id()))
+        {
+            self.external_output = None;
+        }
+        if self.file_view.as_ref().map(|view| view.commit) != self.commit().map(|c| c.id()) {
+            self.file_view = None;
+        }
+        if self.blame.as_ref().map(|view| view.selected) != self.commit().map(|c| c.id()) {
+            self.blame = None;
+        }
+        // The tree browser follows the selected commit
+        if let Some(browsed) = self.tree.as_ref().map(|tree| tree.commit) {
+            match self.commit().map(|commit| commit.id()) {
+                Some(selected) if selected != browsed => {
+                    let read = match &mut self.tree {
+                        Some(tree) => tree.set_commit(&self.repository, selected),
+                        None => Ok(()),
+                    };
+                    // A tree that can't be read, like in a shallow clone, closes the browser
+                    if let Err(e) = read {
+                        self.tree = None;
+                        if self.app_state == AppState::Tree {
+                            self.app_state = AppState::Commits;
+                        }
+                        self.set_status(tree_error(&e));
+                    }
+                }
+                Some(_) => {}
+                None => self.tree = None,
+            }
+        }
+        self.load_diff();
         self.diff_index = 0;
         self.diff_window_length = 1;
-        self.diff_length = self.diff().len();
+        self.diff_length = self.diff.lines.len();
+        self.diff_cursor = None;
+        self.range_anchor = None;
+        let cursor_line = match (&self.blame, &self.file_view) {
+            (Some(view), _) => Some(view.line),
+            (None, Some(_)) => Some(1),
+            (None, None) => None,
+        };
+        if let Some(cursor_line) = cursor_line {
+            let first = self.diff.files.first().map(|file| file.line).unwrap_or(0);
+            let line = first + cursor_line.saturating_sub(1);
+            self.diff_cursor = Some(std::cmp::min(line, self.diff_length.saturating_sub(1)));
+            self.diff_index = line.saturating_sub(first);
+        }
+        self.file_index = self.diff.file_at(self.diff_index);
+    }
+
+    // Keeps the cursor inside of the window when the window scrolls out from under it
+    fn clamp_cursor(&mut self) {
+        if let Some(cursor) = self.diff_cursor {
+            let last = std::cmp::min(self.diff_index + self.diff_window_length, self.diff_length)
+                .saturating_sub(1);
+            self.diff_cursor = Some(std::cmp::min(std::cmp::max(cursor, self.diff_index), last));
+        }
+    }
+
+    // Moves the cursor and scrolls the window just enough to keep it visible
+    fn move_cursor_to(&mut self, line: usize) {
+        let cursor = std::cmp::min(line, self.diff_length.saturating_sub(1));
+        if cursor < self.diff_index {
+            self.diff_index = cursor;
+        } else if cursor >= self.diff_index + self.diff_window_length {
+            self.diff_index = cursor + 1 - self.diff_window_length;
+        }
+        self.diff_cursor = Some(cursor);
+        self.file_index = self.diff.file_at(self.diff_index);
     }
 
-    pub fn resize_diff_window(&mut self, window_length: usize) {
+    pub fn resize_diff_window(&mut self, window_length: usize, window_width: usize) {
+        self.diff_window_width = window_width;
         // the diff_index + window_length can exceed the diff_length when the diff is scrolled
         // using a small window, and then the window is expanded
         if self.diff_index + window_length > self.diff_length {
             self.diff_index = self.diff_length.saturating_sub(window_length);
         }
         self.diff_window_length = window_length;
+        self.clamp_cursor();
     }
 
     pub fn diff_line_scroll(&self) -> (usize, usize, usize) {
@@ -392,20 +2889,127 @@ impl AppModel {
     }
 
     pub fn go_to_first_diff_line(&mut self) {
+        if self.diff_cursor.is_some() {
+            return self.move_cursor_to(0);
+        }
         self.diff_index = 0;
+        self.file_index = self.diff.file_at(self.diff_index);
     }
 
     pub fn go_to_last_diff_line(&mut self) {
-        self.diff_index = self.diff_length.saturating_sub(self.diff_window_length)
+        if self.diff_cursor.is_some() {
+            return self.move_cursor_to(self.diff_length.saturating_sub(1));
+        }
+        self.diff_index = self.diff_length.saturating_sub(self.diff_window_length);
+        self.file_index = self.diff.file_at(self.diff_index);
     }
 
     pub fn increment_diff_line(&mut self) {
+        if let Some(cursor) = self.diff_cursor {
+            return self.move_cursor_to(cursor + 1);
+        }
         if self.diff_index < self.diff_length.saturating_sub(self.diff_window_length) {
             self.diff_index += 1;
         }
+        self.file_index = self.diff.file_at(self.diff_index);
     }
 
     pub fn decrement_diff_line(&mut self) {
+        if let Some(cursor) = self.diff_cursor {
+            return self.move_cursor_to(cursor.saturating_sub(1));
+        }
         self.diff_index = self.diff_index.saturating_sub(1);
+        self.file_index = self.diff.file_at(self.diff_index);
+    }
+
+    fn scroll_diff_to(&mut self, line: usize) {
+        self.diff_index = std::cmp::min(
+            line,
+            self.diff_length.saturating_sub(self.diff_window_length),
+        );
+        self.file_index = self.diff.file_at(self.diff_index);
+        self.clamp_cursor();
+    }
+
+    pub fn scroll_diff_by(&mut self, lines: isize) {
+        let line = if lines < 0 {
+            self.diff_index.saturating_sub(lines.unsigned_abs())
+        } else {
+            self.diff_index + lines as usize
+        };
+        self.scroll_diff_to(line);
+    }
+
+    pub fn next_hunk(&mut self) {
+        if let Some(line) = self.diff.hunks.iter().find(|line| **line > self.diff_index) {
+            self.scroll_diff_to(*line);
+        }
+    }
+
+    pub fn previous_hunk(&mut self) {
+        if let Some(line) = self
+            .diff
+            .hunks
+            .iter()
+            .rev()
+            .find(|line| **line < self.diff_index)
+        {
+            self.scroll_diff_to(*line);
+        }
+    }
+
+    pub fn next_file(&mut self) {
+        if let Some(index) = self
+            .diff
+            .files
+            .iter()
+            .position(|file| file.line > self.diff_index)
+        {
+            self.select_file(index);
+        }
+    }
+
+    pub fn previous_file(&mut self) {
+        if let Some(index) = self
+            .diff
+            .files
+            .iter()
+            .rposition(|file| file.line < self.diff_index)
+        {
+            self.select_file(index);
+        }
+    }
+
+    pub fn diff_files(&self) -> &[DiffFileEntry] {
+        &self.diff.files
+    }
+
+    // The selected file follows the top line of the diff unless it was picked from the file list
+    pub fn file_index(&self) -> Option<usize> {
+        self.file_index
+    }
+
+    // Scrolls the diff so the header of the file is at the top of the window, or as close to it
+    // as the end of the diff allows
+    pub fn select_file(&mut self, index: usize) {
+        if let Some(file) = self.diff.files.get(index) {
+            self.diff_index = std::cmp::min(
+                file.line,
+                self.diff_length.saturating_sub(self.diff_window_length),
+            );
+            self.file_index = Some(index);
+            self.clamp_cursor();
+        }
+    }
+
+    pub fn increment_file(&mut self) {
+        let index = self.file_index.map(|index| index + 1).unwrap_or(0);
+        self.select_file(index);
+    }
+
+    pub fn decrement_file(&mut self) {
+        if let Some(index) = self.file_index {
+            self.select_file(index.saturating_sub(1));
+        }
     }
 }
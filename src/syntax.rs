@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use syntect::highlighting::{
+    HighlightIterator, HighlightState, Highlighter, Theme as SyntaxTheme, ThemeSet,
+};
+use syntect::parsing::{ParseState, SyntaxReference, SyntaxSet};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+
+use crate::model::{DiffLine, DiffLineKind};
+use crate::theme::Theme;
+
+const THEME: &str = "base16-ocean.dark";
+
+// How far the old and new sides of a hunk have been parsed. Context lines are on both sides.
+#[derive(Clone)]
+pub struct HunkState {
+    old: (ParseState, HighlightState),
+    new: (ParseState, HighlightState),
+}
+
+pub struct SyntaxHighlighter {
+    syntaxes: SyntaxSet,
+    theme: SyntaxTheme,
+}
+
+impl SyntaxHighlighter {
+    pub fn new() -> Self {
+        let mut themes = ThemeSet::load_defaults();
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes
+                .themes
+                .remove(THEME)
+                .expect("default theme is missing"),
+        }
+    }
+
+    fn find_syntax(&self, path: &Path) -> Option<&SyntaxReference> {
+        // Some syntaxes are registered under file names like Makefile rather than an extension
+        path.extension()
+            .and_then(|extension| {
+                self.syntaxes
+                    .find_syntax_by_extension(&extension.to_string_lossy())
+            })
+            .or_else(|| {
+                path.file_name().and_then(|name| {
+                    self.syntaxes
+                        .find_syntax_by_extension(&name.to_string_lossy())
+                })
+            })
+    }
+
+    // The state to highlight the first line of a hunk with, None for files of an unknown syntax
+    pub fn start_hunk(&self, path: &Path) -> Option<HunkState> {
+        let syntax = self.find_syntax(path)?;
+        let side = (
+            ParseState::new(syntax),
+            HighlightState::new(&Highlighter::new(&self.theme), Default::default()),
+        );
+        Some(HunkState {
+            old: side.clone(),
+            new: side,
+        })
+    }
+
+    // Restyles the content of a diff line with the syntax colors of the file it belongs to,
+    // carrying `state` on to the next line of the hunk so constructs spanning several lines are
+    // followed. Changed lines keep their color as a background.
+    pub fn highlight(
+        &self,
+        state: &mut HunkState,
+        line: &DiffLine,
+        theme: &Theme,
+    ) -> Spans<'static> {
+        let background = match line.kind {
+            DiffLineKind::Context => None,
+            DiffLineKind::Added => Some((theme.added_background, theme.added_emphasis)),
+            DiffLineKind::Removed => Some((theme.removed_background, theme.removed_emphasis)),
+            _ => return line.spans.clone(),
+        };
+
+        // The first span is the origin column, everything after it is the line content
+        let (origin, content) = match line.spans.0.split_first() {
+            Some(split) => split,
+            None => return line.spans.clone(),
+        };
+        let text: String = content.iter().map(|span| span.content.as_ref()).collect();
+        let newline_text = format!("{}\n", text);
+        let highlighter = Highlighter::new(&self.theme);
+        let parse = |(parser, highlight): &mut (ParseState, HighlightState)| {
+            let operations = parser.parse_line(&newline_text, &self.syntaxes).ok()?;
+            Some(
+                HighlightIterator::new(highlight, &operations, &newline_text, &highlighter)
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let regions = match line.kind {
+            DiffLineKind::Removed => parse(&mut state.old),
+            DiffLineKind::Added => parse(&mut state.new),
+            _ => parse(&mut state.old).and(parse(&mut state.new)),
+        };
+        let regions = match regions {
+            Some(regions) => regions,
+            None => return line.spans.clone(),
+        };
+
+        // Byte offsets at which each syntax color ends
+        let mut colors: Vec<(usize, Color)> = Vec::with_capacity(regions.len());
+        let mut offset = 0;
+        for (style, region) in regions {
+            offset += region.len();
+            let color = style.foreground;
+            colors.push((
+                std::cmp::min(offset, text.len()),
+                Color::Rgb(color.r, color.g, color.b),
+            ));
+        }
+
+        let layered = |style: Style, foreground: Color| {
+            let style = style.fg(foreground);
+            match background {
+                Some((normal, emphasis)) => {
+                    if style.add_modifier.contains(Modifier::REVERSED) {
+                        style.remove_modifier(Modifier::REVERSED).bg(emphasis)
+                    } else {
+                        style.bg(normal)
+                    }
+                }
+                None => style,
+            }
+        };
+
+        let mut spans = vec![match background {
+            Some((normal, _)) => Span::styled(origin.content.to_string(), origin.style.bg(normal)),
+            None => origin.clone(),
+        }];
+        let mut start = 0;
+        let mut color_index = 0;
+        for span in content {
+            let end = start + span.content.len();
+            let mut position = start;
+            // Split the span wherever the syntax color changes inside of it
+            while position < end {
+                while color_index < colors.len() && colors[color_index].0 <= position {
+                    color_index += 1;
+                }
+                let (color_end, color) = colors
+                    .get(color_index)
+                    .copied()
+                    .unwrap_or((end, Color::Reset));
+                let piece_end = std::cmp::min(color_end, end);
+                spans.push(Span::styled(
+                    text[position..piece_end].to_string(),
+                    layered(span.style, color),
+                ));
+                position = piece_end;
+            }
+            start = end;
+        }
+        Spans::from(spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(text: &str) -> DiffLine {
+        DiffLine {
+            kind: DiffLineKind::Context,
+            file: Some(0),
+            spans: Spans::from(vec![Span::raw(" "), Span::raw(text.to_string())]),
+        }
+    }
+
+    #[test]
+    fn carries_block_comments_across_lines() {
+        let syntax = SyntaxHighlighter::new();
+        let theme = Theme::default();
+        let path = Path::new("lib.rs");
+        let mut state = syntax.start_hunk(path).unwrap();
+        let comment = syntax.highlight(&mut state, &context("/* a"), &theme);
+        let inside = syntax.highlight(&mut state, &context("let x = 1;"), &theme);
+        let alone = syntax.highlight(
+            &mut syntax.start_hunk(path).unwrap(),
+            &context("let x = 1;"),
+            &theme,
+        );
+        assert_eq!(inside.0[1].style.fg, comment.0[1].style.fg);
+        assert_ne!(alone.0[1].style.fg, comment.0[1].style.fg);
+    }
+}
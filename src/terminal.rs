@@ -0,0 +1,16 @@
+use std::io::Write;
+
+use crossterm::tty::IsTty;
+
+// Where the interface is drawn. When stdout is redirected, like in $(gitt --pick), the terminal is
+// written to directly so only the results of gitt end up on stdout.
+pub fn output() -> Box<dyn Write> {
+    let stdout = std::io::stdout();
+    if stdout.is_tty() {
+        return Box::new(stdout);
+    }
+    match std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(tty) => Box::new(tty),
+        Err(_) => Box::new(std::io::stderr()),
+    }
+}
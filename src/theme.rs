@@ -0,0 +1,152 @@
+use serde::de::{Deserializer, Error};
+use serde::Deserialize;
+use tui::style::Color;
+
+// The colors of the interface, set in the [theme] section of the config file
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Theme {
+    // Added and removed lines, diffstat bars and line counts
+    #[serde(deserialize_with = "color")]
+    pub added: Color,
+    #[serde(deserialize_with = "color")]
+    pub removed: Color,
+    // Backgrounds of changed lines once their foreground is taken over by the syntax colors, and
+    // of the words that changed inside of them
+    #[serde(deserialize_with = "color")]
+    pub added_background: Color,
+    #[serde(deserialize_with = "color")]
+    pub added_emphasis: Color,
+    #[serde(deserialize_with = "color")]
+    pub removed_background: Color,
+    #[serde(deserialize_with = "color")]
+    pub removed_emphasis: Color,
+    #[serde(deserialize_with = "color")]
+    pub hunk_header: Color,
+    #[serde(deserialize_with = "color")]
+    pub file_header: Color,
+    // The commit, author and date blame shows beside lines from other commits
+    #[serde(deserialize_with = "color")]
+    pub blame: Color,
+    // Secondary text like the diff settings, line numbers and file modes
+    #[serde(deserialize_with = "color")]
+    pub muted: Color,
+    // Background of the selected lines in blame and the file view
+    #[serde(deserialize_with = "color")]
+    pub cursor: Color,
+    #[serde(deserialize_with = "color")]
+    pub picked: Color,
+    #[serde(deserialize_with = "color")]
+    pub marked: Color,
+    #[serde(deserialize_with = "color")]
+    pub status: Color,
+    #[serde(deserialize_with = "color")]
+    pub unstaged: Color,
+    #[serde(deserialize_with = "color")]
+    pub staged: Color,
+    #[serde(deserialize_with = "color")]
+    pub directory: Color,
+    #[serde(deserialize_with = "color")]
+    pub scrollbar: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            added: Color::Green,
+            removed: Color::Red,
+            added_background: Color::Rgb(0x1d, 0x3b, 0x1d),
+            added_emphasis: Color::Rgb(0x2f, 0x6f, 0x2f),
+            removed_background: Color::Rgb(0x4b, 0x1d, 0x1d),
+            removed_emphasis: Color::Rgb(0x8b, 0x2f, 0x2f),
+            hunk_header: Color::Cyan,
+            file_header: Color::Gray,
+            blame: Color::Gray,
+            muted: Color::DarkGray,
+            cursor: Color::DarkGray,
+            picked: Color::Cyan,
+            marked: Color::Yellow,
+            status: Color::Yellow,
+            unstaged: Color::Red,
+            staged: Color::Green,
+            directory: Color::Blue,
+            scrollbar: Color::Gray,
+        }
+    }
+}
+
+// Colors are a name like red or light-blue, #rrggbb or a number from the 256 color palette
+fn parse_color(text: &str) -> Result<Color, String> {
+    let name = text.trim().to_lowercase().replace(['-', '_', ' '], "");
+    let color = match name.as_str() {
+        "default" | "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => {
+            if let Some(hex) = name.strip_prefix('#').filter(|hex| hex.len() == 6) {
+                let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16);
+                match (channel(0), channel(2), channel(4)) {
+                    (Ok(r), Ok(g), Ok(b)) => Color::Rgb(r, g, b),
+                    _ => return Err(format!("Invalid color: {}", text)),
+                }
+            } else {
+                Color::Indexed(
+                    name.parse()
+                        .map_err(|_| format!("Invalid color: {}", text))?,
+                )
+            }
+        }
+    };
+    Ok(color)
+}
+
+// TOML numbers are taken as palette indexes, the same as numbers in quotes
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Index(u8),
+        Text(String),
+    }
+    match Value::deserialize(deserializer)? {
+        Value::Index(index) => Ok(Color::Indexed(index)),
+        Value::Text(text) => parse_color(&text).map_err(D::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names() {
+        assert_eq!(parse_color("red"), Ok(Color::Red));
+        assert_eq!(parse_color("Light-Blue"), Ok(Color::LightBlue));
+        assert_eq!(parse_color("dark_grey"), Ok(Color::DarkGray));
+        assert_eq!(parse_color("default"), Ok(Color::Reset));
+    }
+
+    #[test]
+    fn parses_hex_and_palette_colors() {
+        assert_eq!(parse_color("#1a2B3c"), Ok(Color::Rgb(0x1a, 0x2b, 0x3c)));
+        assert_eq!(parse_color("208"), Ok(Color::Indexed(208)));
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("#gggggg").is_err());
+        assert!(parse_color("256").is_err());
+        assert!(parse_color("purple").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use git2::{ObjectType, Oid, Repository, Tree};
+
+pub struct TreeRow {
+    pub path: PathBuf,
+    pub name: String,
+    pub id: Oid,
+    pub depth: usize,
+    pub mode: i32,
+    pub kind: Option<ObjectType>,
+    // Only known for blobs
+    pub size: Option<usize>,
+    pub expanded: bool,
+}
+
+impl TreeRow {
+    pub fn is_directory(&self) -> bool {
+        self.kind == Some(ObjectType::Tree)
+    }
+
+    // Submodules are gitlinks, entries pointing at a commit in another repository
+    pub fn is_submodule(&self) -> bool {
+        self.kind == Some(ObjectType::Commit)
+    }
+}
+
+// Browses the tree of a commit as a hierarchy of directories that can be expanded in place
+pub struct TreeBrowser {
+    pub commit: Oid,
+    pub rows: Vec<TreeRow>,
+    pub selected: usize,
+    expanded: HashSet<PathBuf>,
+}
+
+impl TreeBrowser {
+    pub fn new(repository: &Repository, commit: Oid) -> Result<Self, git2::Error> {
+        let mut browser = Self {
+            commit,
+            rows: Vec::new(),
+            selected: 0,
+            expanded: HashSet::new(),
+        };
+        browser.refresh(repository)?;
+        Ok(browser)
+    }
+
+    // Points the browser at another commit, keeping the same directories expanded
+    pub fn set_commit(&mut self, repository: &Repository, commit: Oid) -> Result<(), git2::Error> {
+        self.commit = commit;
+        self.refresh(repository)
+    }
+
+    fn refresh(&mut self, repository: &Repository) -> Result<(), git2::Error> {
+        let selected_path = self.rows.get(self.selected).map(|row| row.path.clone());
+        let tree = repository.find_commit(self.commit)?.tree()?;
+        self.rows.clear();
+        self.add_rows(repository, &tree, Path::new(""), 0)?;
+        self.selected = selected_path
+            .and_then(|path| self.rows.iter().position(|row| row.path == path))
+            .unwrap_or_else(|| std::cmp::min(self.selected, self.rows.len().saturating_sub(1)));
+        Ok(())
+    }
+
+    fn add_rows(
+        &mut self,
+        repository: &Repository,
+        tree: &Tree,
+        parent: &Path,
+        depth: usize,
+    ) -> Result<(), git2::Error> {
+        // Directories are listed before files, as most file browsers do
+        let odb = repository.odb()?;
+        let mut entries: Vec<_> = tree.iter().collect();
+        entries.sort_by_key(|entry| entry.kind() != Some(ObjectType::Tree));
+        for entry in entries {
+            let name = entry.name().unwrap_or("INVALID UTF8 IN PATH").to_string();
+            let path = parent.join(&name);
+            let expanded = self.expanded.contains(&path);
+            let size = match entry.kind() {
+                // Only the header is read, loading every blob would be slow for large files
+                Some(ObjectType::Blob) => odb.read_header(entry.id()).ok().map(|(size, _)| size),
+                _ => None,
+            };
+            self.rows.push(TreeRow {
+                path: path.clone(),
+                name,
+                id: entry.id(),
+                depth,
+                mode: entry.filemode(),
+                kind: entry.kind(),
+                size,
+                expanded,
+            });
+            if expanded && entry.kind() == Some(ObjectType::Tree) {
+                let subtree = repository.find_tree(entry.id())?;
+                self.add_rows(repository, &subtree, &path, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn selected_row(&self) -> Option<&TreeRow> {
+        self.rows.get(self.selected)
+    }
+
+    pub fn increment(&mut self) {
+        if self.selected + 1 < self.rows.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn decrement(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn set_expanded(
+        &mut self,
+        repository: &Repository,
+        expanded: bool,
+    ) -> Result<(), git2::Error> {
+        let path = match self.selected_row() {
+            Some(row) if row.is_directory() => row.path.clone(),
+            _ => return Ok(()),
+        };
+        if expanded {
+            self.expanded.insert(path);
+        } else {
+            self.expanded.remove(&path);
+        }
+        self.refresh(repository)
+    }
+
+    // Collapses the selected directory, or moves to the parent directory if there is nothing to
+    // collapse
+    pub fn collapse_or_parent(&mut self, repository: &Repository) -> Result<(), git2::Error> {
+        match self.selected_row() {
+            Some(row) if row.is_directory() && row.expanded => self.set_expanded(repository, false),
+            Some(row) => {
+                if let Some(parent) = row.path.parent() {
+                    if let Some(index) = self.rows.iter().position(|row| row.path == parent) {
+                        self.selected = index;
+                    }
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn format_size(size: usize) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut scaled = size as f64 / 1024.0;
+    let mut unit = 0;
+    while scaled >= 1024.0 && unit < UNITS.len() - 1 {
+        scaled /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", scaled, UNITS[unit])
+}
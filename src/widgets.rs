@@ -50,14 +50,56 @@ impl Widget for VerticalBar {
     }
 }
 
+// How much of the commit list's width a column wants, and the range its width is kept in
+#[derive(Clone, Copy, Debug)]
+pub struct Column {
+    // Percentage of the width
+    pub weight: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ColumnLayout {
+    pub subject: Column,
+    pub author: Column,
+    pub date: Column,
+}
+
+impl Default for ColumnLayout {
+    fn default() -> Self {
+        Self {
+            subject: Column {
+                weight: 72.0,
+                min: Some(50.0),
+                max: None,
+            },
+            author: Column {
+                weight: 18.0,
+                min: Some(20.0),
+                max: Some(40.0),
+            },
+            date: Column {
+                weight: 9.0,
+                min: Some(10.0),
+                max: Some(16.0),
+            },
+        }
+    }
+}
+
 // bounds consists of pairs of variables representing left and right position of the column
-pub fn commit_list_column_width_solver(bounds: &[Variable], window_width: &Variable) -> Solver {
+pub fn commit_list_column_width_solver(
+    bounds: &[Variable],
+    window_width: &Variable,
+    layout: &ColumnLayout,
+) -> Solver {
     let mut solver = Solver::new();
     solver
         .add_constraints(&[
             *window_width | GE(REQUIRED) | 0.0, // positive window width
             bounds[0] | EQ(REQUIRED) | 0.0,     // left align
-            bounds[3] | EQ(REQUIRED) | bounds[4] - 1.0, // right align
+            bounds[3] | EQ(REQUIRED) | (bounds[4] - 1.0), // right align
             bounds[5] | EQ(REQUIRED) | *window_width, // right align
             bounds[2] | GE(REQUIRED) | bounds[1], // no overlap
             bounds[4] | GE(REQUIRED) | bounds[3], // no overlap
@@ -65,23 +107,25 @@ pub fn commit_list_column_width_solver(bounds: &[Variable], window_width: &Varia
             bounds[0] | LE(REQUIRED) | bounds[1],
             bounds[2] | LE(REQUIRED) | bounds[3],
             bounds[4] | LE(REQUIRED) | bounds[5],
-            // preferred widths:
-            bounds[1] - bounds[0] | EQ(WEAK) | *window_width * (72.0 / 100.0),
-            bounds[3] - bounds[2] | EQ(WEAK) | *window_width * (18.0 / 100.0),
-            bounds[5] - bounds[4] | EQ(WEAK) | *window_width * (9.0 / 100.0),
-            // constrain some columns to a range:
-            bounds[3] - bounds[2] | LE(REQUIRED) | 40.0,
-            bounds[3] - bounds[2] | GE(STRONG) | 20.0,
-            bounds[5] - bounds[4] | LE(REQUIRED) | 16.0,
-            bounds[5] - bounds[4] | GE(STRONG) | 10.0,
-            // require one column to have a minimum size
-            bounds[1] - bounds[0] | GE(STRONG) | 50.0,
-            // fixed length
-            //box1.right - box1.left | EQ(WEAK) | 79.0,
-            //box2.right - box2.left | EQ(WEAK) | 20.0,
-            //box3.right - box3.left | EQ(WEAK) | 10.0,
         ])
         .unwrap();
+    let columns = [layout.subject, layout.author, layout.date];
+    for (index, column) in columns.iter().enumerate() {
+        let width = bounds[index * 2 + 1] - bounds[index * 2];
+        // preferred width
+        solver
+            .add_constraint(width.clone() | EQ(WEAK) | (*window_width * (column.weight / 100.0)))
+            .unwrap();
+        // constrain the column to a range
+        if let Some(max) = column.max {
+            solver
+                .add_constraint(width.clone() | LE(REQUIRED) | max)
+                .unwrap();
+        }
+        if let Some(min) = column.min {
+            solver.add_constraint(width | GE(STRONG) | min).unwrap();
+        }
+    }
     solver
         .add_edit_variable(*window_width, STRONG)
         .expect("Unable to add edit variable");
@@ -0,0 +1,379 @@
+use std::str::FromStr;
+
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+
+use crate::model::DiffLineKind;
+use crate::theme::Theme;
+
+// Pairs of lines whose token counts multiply out past this are not diffed, the LCS table would
+// make building the diff for a selected commit too slow
+const MAX_LCS_CELLS: usize = 250_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WordDiffMode {
+    // Whole lines are colored, nothing inside them is emphasised
+    None,
+    // Changed words inside paired lines are emphasised
+    Word,
+    // Changed characters inside paired lines are emphasised
+    Char,
+    // Paired lines are merged into one line with [-old-]{+new+} markers
+    Plain,
+}
+
+impl WordDiffMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Word,
+            Self::Word => Self::Char,
+            Self::Char => Self::Plain,
+            Self::Plain => Self::None,
+        }
+    }
+}
+
+impl FromStr for WordDiffMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "word" => Ok(Self::Word),
+            "char" => Ok(Self::Char),
+            "plain" => Ok(Self::Plain),
+            _ => Err(format!("Unknown word diff mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+fn tokenize(line: &str, mode: WordDiffMode) -> Vec<&str> {
+    if mode == WordDiffMode::Char {
+        return line
+            .char_indices()
+            .map(|(i, c)| &line[i..i + c.len_utf8()])
+            .collect();
+    }
+
+    // Words are runs of identifier characters or runs of whitespace, and every other character
+    // is a token on its own so that punctuation changes stay small
+    fn class(c: char) -> u8 {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<u8> = None;
+    for (i, c) in line.char_indices() {
+        let current = class(c);
+        if let Some(previous) = previous {
+            if previous != current || current == 2 {
+                tokens.push(&line[start..i]);
+                start = i;
+            }
+        }
+        previous = Some(current);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+// Longest common subsequence over the tokens, returned as runs of the same kind of edit
+fn diff_tokens(old: &[&str], new: &[&str]) -> Option<Vec<(Edit, String)>> {
+    if old.len().saturating_mul(new.len()) > MAX_LCS_CELLS {
+        return None;
+    }
+
+    let width = new.len() + 1;
+    let mut table = vec![0usize; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i * width + j] = if old[i] == new[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                std::cmp::max(table[(i + 1) * width + j], table[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut edits: Vec<(Edit, String)> = Vec::new();
+    let mut push = |edit: Edit, token: &str| match edits.last_mut() {
+        Some((last, text)) if *last == edit => text.push_str(token),
+        _ => edits.push((edit, token.to_string())),
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            push(Edit::Equal, old[i]);
+            i += 1;
+            j += 1;
+        } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+            push(Edit::Delete, old[i]);
+            i += 1;
+        } else {
+            push(Edit::Insert, new[j]);
+            j += 1;
+        }
+    }
+    old[i..].iter().for_each(|t| push(Edit::Delete, t));
+    new[j..].iter().for_each(|t| push(Edit::Insert, t));
+    Some(edits)
+}
+
+fn removed_style(theme: &Theme) -> Style {
+    Style::default().fg(theme.removed)
+}
+
+fn added_style(theme: &Theme) -> Style {
+    Style::default().fg(theme.added)
+}
+
+fn emphasis(style: Style) -> Style {
+    style.add_modifier(Modifier::REVERSED)
+}
+
+fn origin_span(origin: char, style: Style) -> Span<'static> {
+    Span::styled(origin.to_string(), style)
+}
+
+fn marked(text: &str, style: Style, open: &str, close: &str) -> Span<'static> {
+    Span::styled(format!("{}{}{}", open, text, close), style)
+}
+
+// Renders one side of a paired line, emphasising the edits of the given kind and dropping the
+// edits that belong to the other side
+fn emphasised_line(
+    origin: char,
+    edits: &[(Edit, String)],
+    kind: Edit,
+    style: Style,
+) -> Spans<'static> {
+    let mut spans = vec![origin_span(origin, style)];
+    spans.extend(edits.iter().filter_map(|(edit, text)| {
+        if *edit == Edit::Equal {
+            Some(Span::styled(text.clone(), style))
+        } else if *edit == kind {
+            Some(Span::styled(text.clone(), emphasis(style)))
+        } else {
+            None
+        }
+    }));
+    Spans::from(spans)
+}
+
+fn marked_line(
+    text: &str,
+    style: Style,
+    open: &str,
+    close: &str,
+) -> (DiffLineKind, Spans<'static>) {
+    (
+        DiffLineKind::Merged,
+        Spans::from(vec![Span::raw(" "), marked(text, style, open, close)]),
+    )
+}
+
+fn merged_line(edits: &[(Edit, String)], theme: &Theme) -> Spans<'static> {
+    let mut spans = vec![Span::raw(" ")];
+    spans.extend(edits.iter().map(|(edit, text)| match edit {
+        Edit::Equal => Span::raw(text.clone()),
+        Edit::Delete => marked(text, removed_style(theme), "[-", "-]"),
+        Edit::Insert => marked(text, added_style(theme), "{+", "+}"),
+    }));
+    Spans::from(spans)
+}
+
+// Collects a run of removed and added lines within a hunk so that they can be paired up with
+// each other once the run ends
+#[derive(Default)]
+pub struct ChangeBuffer {
+    removed: Vec<String>,
+    added: Vec<String>,
+}
+
+impl ChangeBuffer {
+    // Returns true if a line with this origin does not belong to the buffered run of changes
+    pub fn ends_run(&self, origin: char) -> bool {
+        match origin {
+            '+' => false,
+            '-' => !self.added.is_empty(),
+            _ => true,
+        }
+    }
+
+    pub fn push(&mut self, origin: char, line: &str) {
+        match origin {
+            '-' => self.removed.push(line.to_string()),
+            '+' => self.added.push(line.to_string()),
+            _ => unreachable!("only changed lines are buffered"),
+        }
+    }
+
+    pub fn flush(
+        &mut self,
+        mode: WordDiffMode,
+        theme: &Theme,
+    ) -> Vec<(DiffLineKind, Spans<'static>)> {
+        let removed = std::mem::take(&mut self.removed);
+        let added = std::mem::take(&mut self.added);
+
+        // The n-th removed line is paired with the n-th added line
+        let pairs: Vec<Option<Vec<(Edit, String)>>> = removed
+            .iter()
+            .zip(added.iter())
+            .map(|(old, new)| {
+                if mode == WordDiffMode::None {
+                    return None;
+                }
+                let tokenize_mode = if mode == WordDiffMode::Plain {
+                    WordDiffMode::Word
+                } else {
+                    mode
+                };
+                let old_tokens = tokenize(old, tokenize_mode);
+                let new_tokens = tokenize(new, tokenize_mode);
+                diff_tokens(&old_tokens, &new_tokens).filter(|edits| {
+                    // Lines with nothing in common are replacements, emphasising all of them
+                    // adds noise without pointing anything out
+                    edits
+                        .iter()
+                        .any(|(edit, text)| *edit == Edit::Equal && !text.trim().is_empty())
+                })
+            })
+            .collect();
+
+        let mut lines = Vec::with_capacity(removed.len() + added.len());
+        if mode == WordDiffMode::Plain {
+            for (index, pair) in pairs.iter().enumerate() {
+                match pair {
+                    Some(edits) => lines.push((DiffLineKind::Merged, merged_line(edits, theme))),
+                    None => {
+                        lines.push(marked_line(
+                            &removed[index],
+                            removed_style(theme),
+                            "[-",
+                            "-]",
+                        ));
+                        lines.push(marked_line(&added[index], added_style(theme), "{+", "+}"));
+                    }
+                }
+            }
+            lines.extend(
+                removed[pairs.len()..]
+                    .iter()
+                    .map(|line| marked_line(line, removed_style(theme), "[-", "-]")),
+            );
+            lines.extend(
+                added[pairs.len()..]
+                    .iter()
+                    .map(|line| marked_line(line, added_style(theme), "{+", "+}")),
+            );
+            return lines;
+        }
+
+        for (index, line) in removed.iter().enumerate() {
+            lines.push((
+                DiffLineKind::Removed,
+                match pairs.get(index) {
+                    Some(Some(edits)) => {
+                        emphasised_line('-', edits, Edit::Delete, removed_style(theme))
+                    }
+                    _ => Spans::from(vec![
+                        origin_span('-', removed_style(theme)),
+                        Span::styled(line.clone(), removed_style(theme)),
+                    ]),
+                },
+            ));
+        }
+        for (index, line) in added.iter().enumerate() {
+            lines.push((
+                DiffLineKind::Added,
+                match pairs.get(index) {
+                    Some(Some(edits)) => {
+                        emphasised_line('+', edits, Edit::Insert, added_style(theme))
+                    }
+                    _ => Spans::from(vec![
+                        origin_span('+', added_style(theme)),
+                        Span::styled(line.clone(), added_style(theme)),
+                    ]),
+                },
+            ));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str, mode: WordDiffMode) -> Vec<(Edit, String)> {
+        diff_tokens(&tokenize(old, mode), &tokenize(new, mode)).unwrap()
+    }
+
+    #[test]
+    fn splits_words_whitespace_and_punctuation() {
+        assert_eq!(
+            tokenize("let x_1 = f(a);", WordDiffMode::Word),
+            vec!["let", " ", "x_1", " ", "=", " ", "f", "(", "a", ")", ";"]
+        );
+        assert_eq!(tokenize("ab", WordDiffMode::Char), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn finds_the_changed_words() {
+        assert_eq!(
+            diff("let a = 1;", "let b = 1;", WordDiffMode::Word),
+            vec![
+                (Edit::Equal, "let ".to_string()),
+                (Edit::Delete, "a".to_string()),
+                (Edit::Insert, "b".to_string()),
+                (Edit::Equal, " = 1;".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_the_changed_characters() {
+        assert_eq!(
+            diff("color", "colour", WordDiffMode::Char),
+            vec![
+                (Edit::Equal, "colo".to_string()),
+                (Edit::Insert, "u".to_string()),
+                (Edit::Equal, "r".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_trailing_whitespace() {
+        assert_eq!(
+            diff("x = 1;", "x = 1;  ", WordDiffMode::Word),
+            vec![
+                (Edit::Equal, "x = 1;".to_string()),
+                (Edit::Insert, "  ".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn gives_up_on_long_lines() {
+        let long: Vec<&str> = vec!["x"; 1000];
+        assert!(diff_tokens(&long, &long).is_none());
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+use git2::{Repository, Signature, Time};
+
+// A repository with a merge, where only some commits touch docs/
+fn fixture(directory: &Path) {
+    let _ = std::fs::remove_dir_all(directory);
+    let repository = Repository::init(directory).unwrap();
+    let mut time = 0;
+    let mut commit = |message: &str, files: &[(&str, &str)], parents: &[git2::Oid]| {
+        time += 60;
+        let signature = Signature::new("Ann Dev", "ann@example.com", &Time::new(time, 0)).unwrap();
+        // Each commit starts from its first parent's files
+        let mut index = repository.index().unwrap();
+        index.clear().unwrap();
+        if let Some(parent) = parents.first() {
+            let tree = repository.find_commit(*parent).unwrap().tree().unwrap();
+            index.read_tree(&tree).unwrap();
+        }
+        for (path, content) in files {
+            let path = Path::new(path);
+            std::fs::create_dir_all(directory.join(path.parent().unwrap())).unwrap();
+            std::fs::write(directory.join(path), content).unwrap();
+            index.add_path(path).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+        let parents: Vec<_> = parents
+            .iter()
+            .map(|id| repository.find_commit(*id).unwrap())
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        repository
+            .commit(None, &signature, &signature, message, &tree, &parents)
+            .unwrap()
+    };
+    let base = commit("Add the readme", &[("README", "gitt\n")], &[]);
+    let docs = commit("Write the docs", &[("docs/usage.md", "Run it\n")], &[base]);
+    let code = commit(
+        "Write the code",
+        &[("src/main.rs", "fn main() {}\n")],
+        &[base],
+    );
+    let merge = commit(
+        "Merge the docs",
+        &[("docs/usage.md", "Run it\n")],
+        &[code, docs],
+    );
+    repository
+        .reference("refs/heads/main", merge, true, "")
+        .unwrap();
+    repository.set_head("refs/heads/main").unwrap();
+    repository
+        .tag_lightweight("v1", &repository.find_object(base, None).unwrap(), false)
+        .unwrap();
+}
+
+fn run(directory: &Path, args: &[&str]) -> Output {
+    // Settings from the user's config files would change the output
+    let home = directory.with_extension("home");
+    Command::new(env!("CARGO_BIN_EXE_gitt"))
+        .arg("--working-directory")
+        .arg(directory)
+        .args(args)
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", &home)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .output()
+        .unwrap()
+}
+
+fn gitt(directory: &Path, args: &[&str]) -> String {
+    let output = run(directory, args);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn prints_commits_with_a_format_and_graph() {
+    let directory = std::env::temp_dir().join(format!("gitt-print-{}", std::process::id()));
+    fixture(&directory);
+    assert_eq!(
+        gitt(&directory, &["--graph", "--format", "%s%d"]),
+        "*-\\ Merge the docs (HEAD -> main)\n\
+         * | Write the code\n\
+         | * Write the docs\n\
+         *-/ Add the readme (tag: v1)\n"
+    );
+    // The merge is compared with its first parent, where the docs are new
+    assert_eq!(
+        gitt(&directory, &["--print", "--format", "%s", "--", "docs"]),
+        "Merge the docs\nWrite the docs\n"
+    );
+    // A range leaves out what its start reaches, and lines are followed back from its end
+    assert_eq!(
+        gitt(&directory, &["--print", "--format", "%s", "v1..main"]),
+        "Merge the docs\nWrite the code\nWrite the docs\n"
+    );
+    assert_eq!(
+        gitt(
+            &directory,
+            &[
+                "--print",
+                "--format",
+                "%s",
+                "-L",
+                "1,1:src/main.rs",
+                "v1..main"
+            ]
+        ),
+        "Write the code\n"
+    );
+    let output = run(&directory, &["--print", "-L", "1,5:src/main.rs"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("src/main.rs has only 1 line"));
+    std::fs::remove_dir_all(&directory).unwrap();
+}